@@ -1,4 +1,4 @@
-use crate::{assets::GameAssets, AppState, ingame, title_screen, splash};
+use crate::{assets::GameAssets, AppState, ingame, title_screen, splash, floor, difficulty, food};
 use bevy::{asset::Asset, ecs::system::SystemParam, gltf::Gltf, prelude::*};
 use bevy_kira_audio::AudioSource;
 use std::marker::PhantomData;
@@ -33,6 +33,8 @@ impl Default for QueueState {
 #[derive(Default, Resource)]
 pub struct AssetsLoading {
     pub asset_handles: Vec<(HandleUntyped, String)>,
+    // ones the game has its own fallback for, failing to load doesn't stop it
+    pub optional_handles: Vec<HandleUntyped>,
 }
 
 #[derive(SystemParam)]
@@ -45,6 +47,7 @@ pub struct AssetsHandler<'w, 's> {
     state: Res<'w, State<AppState>>,
     next_state: ResMut<'w, NextState<AppState>>,
     queued_state: ResMut<'w, QueueState>,
+    pub track_settings: Res<'w, floor::track::TrackSettings>,
 
     #[system_param(ignore)]
     phantom: PhantomData<&'s ()>,
@@ -58,6 +61,11 @@ impl<'w, 's> AssetsHandler<'w, 's> {
             .push((asset.clone_untyped(), path.to_string()));
     }
 
+    fn add_optional_asset<T: Asset>(&mut self, asset: &mut Handle<T>, path: &str) {
+        self.add_asset(asset, path);
+        self.assets_loading.optional_handles.push(asset.clone_untyped());
+    }
+
     pub fn load(
         &mut self,
         queued_state: AppState,
@@ -80,6 +88,22 @@ impl<'w, 's> AssetsHandler<'w, 's> {
         self.add_asset(audio, path);
     }
 
+    pub fn add_image(&mut self, image: &mut Handle<Image>, path: &str) {
+        self.add_asset(image, path);
+    }
+
+    pub fn add_level(&mut self, level: &mut Handle<floor::level::Level>, path: &str) {
+        self.add_asset(level, path);
+    }
+
+    pub fn add_difficulty(&mut self, difficulty: &mut Handle<difficulty::DifficultyConfig>, path: &str) {
+        self.add_optional_asset(difficulty, path);
+    }
+
+    pub fn add_collectibles(&mut self, collectibles: &mut Handle<food::collectible::CollectibleSet>, path: &str) {
+        self.add_optional_asset(collectibles, path);
+    }
+
     pub fn add_glb(&mut self, glb: &mut Handle<Gltf>, path: &str) {
         self.add_asset(glb, path);
     }
@@ -133,6 +157,8 @@ fn check_assets_ready(mut assets_handler: AssetsHandler) {
     let mut ready = true;
     for (handle, path) in assets_handler.assets_loading.asset_handles.iter() {
         match assets_handler.asset_server.get_load_state(handle) {
+            // whatever uses it goes without
+            LoadState::Failed if assets_handler.assets_loading.optional_handles.contains(handle) => {}
             LoadState::Failed => {
                 panic!("An asset had an error: {:?}", handle);
             }
@@ -151,6 +177,7 @@ fn check_assets_ready(mut assets_handler: AssetsHandler) {
     if ready {
         println!("ready!");
         assets_handler.assets_loading.asset_handles = vec![]; // clear list since we've loaded everything
+        assets_handler.assets_loading.optional_handles = vec![];
         assets_handler
             .next_state
             .set(assets_handler.queued_state.state);
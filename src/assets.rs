@@ -1,4 +1,4 @@
-use crate::asset_loading;
+use crate::{asset_loading, difficulty, floor, food};
 use bevy::gltf::Gltf;
 use bevy::prelude::*;
 use bevy_kira_audio::AudioSource;
@@ -13,7 +13,6 @@ impl Plugin for AssetsPlugin {
 #[derive(Default, Resource)]
 pub struct GameAssets {
     pub font: Handle<Font>,
-    pub donut: Handle<Gltf>,
     pub TJ: Handle<Gltf>,
 
     pub blip: Handle<AudioSource>,
@@ -24,6 +23,13 @@ pub struct GameAssets {
     pub level_bgm: Handle<AudioSource>,
     pub title_screen_bgm: Handle<AudioSource>,
 
+    pub track_heightmap: Handle<Image>,
+    pub level: Handle<floor::level::Level>,
+    pub difficulty: Handle<difficulty::DifficultyConfig>,
+    pub collectibles: Handle<food::collectible::CollectibleSet>,
+
+    pub donut_mesh: Handle<Mesh>,
+
     pub bevy_icon: asset_loading::GameTexture,
     pub title_screen_logo: asset_loading::GameTexture,
 }
@@ -64,6 +64,11 @@ impl<'w, 's> GameAudio<'w, 's> {
         self.sound_channel.set_volume(0.2);
         self.sound_channel.play(handle.clone());
     }
+    // the same as play_sfx but sped up or slowed down, which raises or lowers the pitch
+    pub fn play_sfx_pitched(&mut self, handle: &Handle<AudioSource>, playback_rate: f64) {
+        self.sound_channel.set_volume(0.2);
+        self.sound_channel.play(handle.clone()).with_playback_rate(playback_rate);
+    }
     pub fn play_talk(&mut self, handle: &Handle<AudioSource>) {
         self.talk_channel.play(handle.clone());
     }
@@ -0,0 +1,450 @@
+use bevy::prelude::*;
+use bevy::render::primitives::Aabb;
+use bevy_rapier3d::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use bevy::gltf::Gltf;
+use crate::{assets, difficulty, floor, food, player, rng};
+
+static BENCH_SEED: u64 = 1234;
+// a few thousand rows get recycled
+static BENCH_FRAMES: u32 = 12000;
+// about a third of a row every frame
+static BENCH_STEP: f32 = 0.1;
+static BENCH_DONUT_FRAMES: u32 = 100;
+
+// runs the floor systems without a window for a fixed number of frames
+// and prints how long they took and how much they left lying around.
+// it goes once the way floors were before rows became one mesh, as a baseline,
+// once scanning every block like update_floors used to, once with
+// the grid lookup and once with a heightfield per row so they can be compared.
+// the grid runs also check FloorManager::height_at still agrees with every block,
+// and every run checks each donut is somewhere the player can get to.
+// start the game with "--bench-floors" to run it
+pub fn run() {
+    bench_per_cube();
+    println!();
+    bench("full scan", update_floors_full_scan, floor::ColliderMode::Cuboids, false);
+    println!();
+    bench("grid", floor::update_floors, floor::ColliderMode::Cuboids, true);
+    println!();
+    bench("heightfield", floor::update_floors, floor::ColliderMode::Heightfield, true);
+}
+
+fn bench<M>(name: &str, update_floors: impl IntoSystemConfig<M>, collider_mode: floor::ColliderMode, check_heights: bool) {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin::default())
+        .add_asset::<Mesh>()
+        .add_asset::<StandardMaterial>()
+        .add_asset::<Gltf>()
+        .add_event::<food::SpawnFoodEvent>()
+        .add_event::<food::FoodCollectedEvent>()
+        .insert_resource(rng::RunRng::from_seed(BENCH_SEED))
+        .insert_resource(floor::track::TrackSettings {
+            collider_mode,
+            ..default()
+        })
+        .init_resource::<assets::GameAssets>()
+        .init_resource::<floor::palette::Palette>()
+        .init_resource::<difficulty::DifficultyProfile>()
+        .init_resource::<floor::FloorManager>()
+        .init_resource::<UnreachableDonuts>()
+        .init_resource::<food::collectible::Collectibles>()
+        .init_resource::<food::FoodStats>()
+        .init_resource::<food::PatternGroups>()
+        .init_resource::<player::jump::JumpSettings>()
+        .add_startup_system(setup)
+        .add_systems((
+                move_forward,
+                update_floors,
+                floor::shift_floors,
+                food::cull_food,
+                apply_system_buffers,
+                floor::origin::shift_origin,
+                food::spawn_food,
+                apply_system_buffers,
+                check_donuts,
+            ).chain()
+        );
+
+    // startup
+    app.update();
+    let starting_materials = app.world.resource::<Assets<StandardMaterial>>().len();
+
+    let start = Instant::now();
+    let mut slowest = 0.0_f32;
+    for _ in 0..BENCH_FRAMES {
+        let frame_start = Instant::now();
+        app.update();
+        slowest = slowest.max(frame_start.elapsed().as_secs_f32());
+    }
+    let elapsed = start.elapsed().as_secs_f32();
+
+    let world = &mut app.world;
+    let chunks = world.query::<&floor::FloorChunk>().iter(world).count();
+    let blocks = world.query::<&floor::FloorChunk>().iter(world).map(|chunk| chunk.blocks.len()).sum::<usize>();
+    let colliders = world.query_filtered::<&Collider, Without<Sensor>>().iter(world).count();
+    let drawn = world.query::<&Handle<Mesh>>().iter(world).count();
+    println!("{}", name);
+    println!("frames:         {}", BENCH_FRAMES);
+    let player_x = world.query_filtered::<&Transform, With<player::Player>>().single(world).translation.x;
+    println!("distance:       {:.2} (at x {:.2})", world.resource::<floor::FloorManager>().track_x(player_x), player_x);
+    println!("average frame:  {:.3}ms", (elapsed / BENCH_FRAMES as f32) * 1000.0);
+    println!("slowest frame:  {:.3}ms", slowest * 1000.0);
+    println!("entities:       {}", world.entities().len());
+    println!("floor rows:     {}", chunks);
+    println!("floor blocks:   {}", blocks);
+    println!("colliders:      {}", colliders);
+    println!("draw calls:     {}", drawn);
+    println!("donuts:         {}", world.query::<&food::Food>().iter(world).count());
+    println!("missed donuts:  {}", world.resource::<food::FoodStats>().missed);
+    println!("pooled donuts:  {}", world.query::<&food::PooledFood>().iter(world).count());
+    println!("unreachable:    {}", world.resource::<UnreachableDonuts>().0);
+    println!("meshes:         {}", world.resource::<Assets<Mesh>>().len());
+    println!("materials:      {}", world.resource::<Assets<StandardMaterial>>().len());
+
+    // recycling rows and spawning donuts shouldn't make anything new to draw them with
+    assert_eq!(world.resource::<Assets<StandardMaterial>>().len(), starting_materials, "floor or donut materials leaked");
+    // missed donuts should come back out of the pool rather than pile up
+    let donut_entities = world.query::<&food::Food>().iter(world).count() + world.query::<&food::PooledFood>().iter(world).count();
+    assert!(donut_entities < world.resource::<food::FoodStats>().missed, "missed donuts weren't reused");
+    // a donut nobody can get to means the placement is broken
+    assert_eq!(world.resource::<UnreachableDonuts>().0, 0, "donuts were put somewhere the player can't reach");
+
+    // the full scan grows blocks without telling FloorManager so it's left out
+    if check_heights {
+        let stale = stale_heights(world);
+        println!("stale heights:  {}", stale);
+        assert_eq!(stale, 0, "height_at disagrees with the floor blocks");
+    }
+}
+
+// blocks whose height FloorManager::height_at or column_profile doesn't agree with
+fn stale_heights(world: &mut World) -> usize {
+    let rows = world
+        .query::<(&floor::FloorChunk, &Transform)>()
+        .iter(world)
+        .map(|(chunk, transform)| (transform.translation.x, chunk.blocks.clone()))
+        .collect::<Vec<_>>();
+    let floor_manager = world.resource::<floor::FloorManager>();
+
+    rows
+        .iter()
+        .map(|(x, blocks)| {
+            let profile = floor_manager.column_profile(*x);
+            let stale_blocks = blocks
+                .iter()
+                .filter(|block| {
+                    let z = block.z * floor::FLOOR_CUBE_SIZE;
+                    let top = block.height * floor::FLOOR_CUBE_SIZE / 2.0;
+                    floor_manager.height_at(*x, z) != Some(top) || !profile.contains(&(z, top))
+                })
+                .count();
+            // and nothing left over from blocks that have gone
+            stale_blocks + profile.len().saturating_sub(blocks.len())
+        })
+        .sum()
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut palette: ResMut<floor::palette::Palette>,
+    mut floor_manager: ResMut<floor::FloorManager>,
+    mut game_assets: ResMut<assets::GameAssets>,
+    mut collectibles: ResMut<food::collectible::Collectibles>,
+    mut run_rng: ResMut<rng::RunRng>,
+    track_settings: Res<floor::track::TrackSettings>,
+    difficulty: Res<difficulty::DifficultyProfile>,
+    mut food_spawn_event_writer: EventWriter<food::SpawnFoodEvent>,
+) {
+    game_assets.donut_mesh = meshes.add(food::donut_mesh());
+    collectibles.start_run(None, &mut palette, &mut materials);
+
+    let track_generator = Box::new(BenchTrack(floor::track::SegmentTrack::new(floor::track::DEFAULT_SEGMENT_WEIGHTS.to_vec())));
+    floor::setup_floor(&mut commands, &mut meshes, &mut materials, &mut palette, &mut floor_manager, &mut run_rng, track_generator, track_settings.collider_mode, &difficulty, &mut food_spawn_event_writer);
+
+    let player = player::Player::new(&mut run_rng);
+    commands.spawn((
+        TransformBundle::from(Transform::from_xyz(0.0, 0.5, 0.0)),
+        Velocity::linear(Vec3::X * player.speed),
+        player,
+    ));
+    commands.spawn((
+        TransformBundle::from(Transform::from_xyz(-2.8, 1.0, 0.0)),
+        Camera3d::default(),
+    ));
+}
+
+// the usual segments without the track wearing away every lap, so there's
+// the same amount of floor to get through the whole way
+struct BenchTrack(floor::track::SegmentTrack);
+
+impl floor::track::TrackGenerator for BenchTrack {
+    fn next_row(&mut self, row: i32, track_rng: &mut rng::RunRng) -> floor::FloorRow {
+        self.0.next_row(row, track_rng)
+    }
+
+    fn is_endless(&self) -> bool {
+        false
+    }
+}
+
+fn move_forward(
+    mut players: Query<&mut Transform, (With<player::Player>, Without<Camera3d>)>,
+    mut cameras: Query<&mut Transform, (With<Camera3d>, Without<player::Player>)>,
+    mut food_spawn_event_writer: EventWriter<food::SpawnFoodEvent>,
+    mut frame: Local<u32>,
+) {
+    *frame += 1;
+    // going through every pattern in turn
+    if frame.is_multiple_of(BENCH_DONUT_FRAMES) {
+        let patterns = &food::pattern::DEFAULT_PATTERN_WEIGHTS;
+        food_spawn_event_writer.send(food::SpawnFoodEvent {
+            position: None,
+            pattern: patterns[(*frame / BENCH_DONUT_FRAMES) as usize % patterns.len()].0,
+        });
+    }
+
+    for mut player in &mut players {
+        // weave across the track so blocks keep growing and colliders keep changing
+        player.translation.x += BENCH_STEP;
+        player.translation.z = (*frame as f32 * 0.05).sin() * 3.0;
+
+        for mut camera in &mut cameras {
+            camera.translation.x = player.translation.x - 2.8;
+        }
+    }
+}
+
+// the way update_floors used to work, every block checked every frame
+fn update_floors_full_scan(
+    mut commands: Commands,
+    mut chunks: Query<(Entity, &mut floor::FloorChunk, &Transform, &Handle<Mesh>)>,
+    players: Query<&Transform, (With<player::Player>, Without<floor::FloorChunk>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut colliders: Local<HashMap<(i32, i32), Entity>>,
+) {
+    for p in &players {
+        let player_translation = Vec3::new(p.translation.x, 0.0, p.translation.z);
+        let mut nearby = HashMap::new();
+        for (entity, mut chunk, transform, mesh) in &mut chunks {
+            let row = chunk.row;
+            let mut grew = false;
+            for floor in chunk.blocks.iter_mut() {
+                let floor_translation = Vec3::new(transform.translation.x, 0.0, floor.z * floor::FLOOR_CUBE_SIZE);
+                let distance = (floor_translation - player_translation).length();
+                if distance <= 2.0 && floor_translation.x > player_translation.x {
+                    let cell = (row, floor.z as i32);
+                    let collider = colliders.remove(&cell).unwrap_or_else(|| {
+                        let half_size = floor::FLOOR_CUBE_SIZE / 2.0;
+                        commands.spawn((
+                            TransformBundle::from(Transform::from_translation(floor_translation)),
+                            RigidBody::Fixed,
+                            Collider::cuboid(half_size, half_size * floor.height, half_size),
+                            floor::FloorCollider { row, z: floor.z },
+                        )).id()
+                    });
+                    nearby.insert(cell, collider);
+                }
+
+                if distance < 0.5 && floor_translation.x < (player_translation.x - (floor::FLOOR_CUBE_SIZE / 2.0)) {
+                    floor.height += floor::FLOOR_CUBE_SIZE * 2.0;
+                    grew = true;
+                }
+            }
+
+            if grew {
+                if let Some(mesh) = meshes.get_mut(mesh) {
+                    *mesh = floor::row_mesh::build_row_mesh(&chunk.blocks);
+                }
+                commands.entity(entity).remove::<Aabb>();
+            }
+        }
+
+        for (_, collider) in colliders.drain() {
+            commands.entity(collider).despawn_recursive();
+        }
+        *colliders = nearby;
+    }
+}
+
+// one entity per block with a cube of its own and a material of its own,
+// grown by scaling the cube, on the original flat strip
+#[derive(Component)]
+struct CubeFloor {
+    height: f32,
+}
+
+#[derive(Resource, Default)]
+struct CubeFloors {
+    cube_mesh: Handle<Mesh>,
+    next_row: i32,
+}
+
+fn bench_per_cube() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin::default())
+        .add_asset::<Mesh>()
+        .add_asset::<StandardMaterial>()
+        .add_event::<food::SpawnFoodEvent>()
+        .insert_resource(rng::RunRng::from_seed(BENCH_SEED))
+        .init_resource::<CubeFloors>()
+        .add_startup_system(setup_per_cube)
+        .add_systems((move_forward, update_cubes, shift_cubes).chain());
+
+    app.update();
+    let start = Instant::now();
+    let mut slowest = 0.0_f32;
+    for _ in 0..BENCH_FRAMES {
+        let frame_start = Instant::now();
+        app.update();
+        slowest = slowest.max(frame_start.elapsed().as_secs_f32());
+    }
+    let elapsed = start.elapsed().as_secs_f32();
+
+    let world = &mut app.world;
+    println!("per cube");
+    println!("frames:         {}", BENCH_FRAMES);
+    println!("average frame:  {:.3}ms", (elapsed / BENCH_FRAMES as f32) * 1000.0);
+    println!("slowest frame:  {:.3}ms", slowest * 1000.0);
+    println!("entities:       {}", world.entities().len());
+    println!("floor blocks:   {}", world.query::<&CubeFloor>().iter(world).count());
+    println!("colliders:      {}", world.query::<&Collider>().iter(world).count());
+    println!("draw calls:     {}", world.query::<&Handle<Mesh>>().iter(world).count());
+    println!("meshes:         {}", world.resource::<Assets<Mesh>>().len());
+    println!("materials:      {}", world.resource::<Assets<StandardMaterial>>().len());
+}
+
+fn setup_per_cube(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cube_floors: ResMut<CubeFloors>,
+    mut run_rng: ResMut<rng::RunRng>,
+) {
+    cube_floors.cube_mesh = meshes.add(Mesh::from(shape::Cube { size: floor::FLOOR_CUBE_SIZE }));
+    spawn_cubes(&mut commands, &mut materials, &mut cube_floors, floor::NUMBER_OF_LIVE_ROWS);
+
+    let player = player::Player::new(&mut run_rng);
+    commands.spawn((
+        TransformBundle::from(Transform::from_xyz(0.0, 0.5, 0.0)),
+        player,
+    ));
+    commands.spawn((
+        TransformBundle::from(Transform::from_xyz(-2.8, 1.0, 0.0)),
+        Camera3d::default(),
+    ));
+}
+
+fn spawn_cubes(
+    commands: &mut Commands,
+    materials: &mut Assets<StandardMaterial>,
+    cube_floors: &mut CubeFloors,
+    rows: i32,
+) {
+    let columns = floor::NUMBER_OF_COLUMNS / 2;
+    for x in cube_floors.next_row..(cube_floors.next_row + rows) {
+        for z in -columns..columns {
+            let height = 2.0;
+            commands.spawn((
+                TransformBundle::from(Transform::from_xyz(x as f32 * floor::FLOOR_CUBE_SIZE, 0.0, z as f32 * floor::FLOOR_CUBE_SIZE)),
+                CubeFloor { height },
+                ComputedVisibility::default(),
+                Visibility::Visible,
+            )).with_children(|parent| {
+                parent.spawn(PbrBundle {
+                    mesh: cube_floors.cube_mesh.clone_weak(),
+                    material: materials.add(StandardMaterial {
+                        base_color: Color::hex(crate::FLOOR_COLOR).unwrap(),
+                        ..default()
+                    }),
+                    transform: Transform::from_scale(Vec3::new(1.0, height, 1.0)),
+                    ..default()
+                });
+            });
+        }
+    }
+    cube_floors.next_row += rows;
+}
+
+fn update_cubes(
+    mut commands: Commands,
+    mut floors: Query<(Entity, &mut CubeFloor, &Transform, &Children)>,
+    players: Query<&Transform, (With<player::Player>, Without<CubeFloor>)>,
+    mut transforms: Query<&mut Transform, (Without<player::Player>, Without<CubeFloor>)>,
+) {
+    for p in &players {
+        for (entity, mut floor, transform, children) in &mut floors {
+            let player_translation = Vec3::new(p.translation.x, 0.0, p.translation.z);
+            let floor_translation = Vec3::new(transform.translation.x, 0.0, transform.translation.z);
+            let distance = (floor_translation - player_translation).length();
+            if distance > 2.0 || floor_translation.x <= player_translation.x {
+                commands.entity(entity)
+                    .remove::<RigidBody>()
+                    .remove::<Collider>();
+            }
+            if distance <= 2.0 && floor_translation.x > player_translation.x {
+                let half_size = floor::FLOOR_CUBE_SIZE / 2.0;
+                commands.entity(entity)
+                    .insert((RigidBody::Fixed, Collider::cuboid(half_size, half_size * floor.height, half_size)));
+            }
+
+            if distance < 0.5 && floor_translation.x < (player_translation.x - (floor::FLOOR_CUBE_SIZE / 2.0)) {
+                floor.height += floor::FLOOR_CUBE_SIZE * 2.0;
+                for child_entity in children {
+                    if let Ok(mut child_transform) = transforms.get_mut(*child_entity) {
+                        child_transform.scale.y = floor.height;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn shift_cubes(
+    mut commands: Commands,
+    floors: Query<(Entity, &Transform), With<CubeFloor>>,
+    cameras: Query<&Transform, With<Camera3d>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cube_floors: ResMut<CubeFloors>,
+) {
+    let mut rows = HashSet::new();
+    for (entity, transform) in &floors {
+        for camera in &cameras {
+            if transform.translation.x < camera.translation.x {
+                rows.insert((transform.translation.x / floor::FLOOR_CUBE_SIZE).round() as i32);
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+
+    if !rows.is_empty() {
+        spawn_cubes(&mut commands, &mut materials, &mut cube_floors, rows.len() as i32);
+    }
+}
+
+#[derive(Resource, Default)]
+struct UnreachableDonuts(usize);
+
+// goes over every donut as it's spawned, while the floor is still how spawn_food saw it
+fn check_donuts(
+    donuts: Query<&Transform, Added<food::Food>>,
+    players: Query<(&player::Player, &Transform)>,
+    floor_manager: Res<floor::FloorManager>,
+    jump_settings: Res<player::jump::JumpSettings>,
+    mut unreachable: ResMut<UnreachableDonuts>,
+) {
+    for (player, transform) in &players {
+        let jump = player::JumpModel::new(player, &jump_settings);
+        let mut reachability = food::Reachability::new(&floor_manager, &jump, transform.translation);
+        for donut in &donuts {
+            if !reachability.is_reachable(donut.translation) {
+                unreachable.0 += 1;
+            }
+        }
+    }
+}
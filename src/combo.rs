@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use crate::{player::state, AppState};
+
+// how long after a pickup the next one still counts toward the streak, in
+// real seconds so slow-motion doesn't stretch it
+static COMBO_WINDOW: f32 = 2.0;
+// the multiplier goes up by one every this many pickups in a row
+static PICKUPS_PER_LEVEL: usize = 3;
+static MAX_MULTIPLIER: usize = 5;
+// how much higher the pickup sound gets with every pickup in the streak
+static PITCH_STEP: f64 = 0.05;
+static MAX_PITCH: f64 = 2.0;
+
+pub struct ComboPlugin;
+impl Plugin for ComboPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Combo>()
+            .add_system(start_combo.in_schedule(OnEnter(AppState::InGame)))
+            .add_system(update_combo.in_set(OnUpdate(AppState::InGame)));
+    }
+}
+
+// donuts picked up one after another without the window running out.
+// when it does run out the multiplier drops a level and the window starts
+// again, so a streak winds down instead of vanishing. stalling ends it outright
+#[derive(Resource, Default)]
+pub struct Combo {
+    pub streak: usize,
+    // the longest streak this run
+    pub best: usize,
+    window: f32,
+}
+
+impl Combo {
+    // counts a pickup and hands back the multiplier it gets
+    pub fn collect(&mut self) -> usize {
+        self.streak += 1;
+        self.best = self.best.max(self.streak);
+        self.window = COMBO_WINDOW;
+        self.multiplier()
+    }
+
+    pub fn multiplier(&self) -> usize {
+        (1 + (self.streak.saturating_sub(1) / PICKUPS_PER_LEVEL)).min(MAX_MULTIPLIER)
+    }
+
+    // the first pickup sounds normal, every one after it a bit higher
+    pub fn pitch(&self) -> f64 {
+        (1.0 + (self.streak.saturating_sub(1) as f64 * PITCH_STEP)).min(MAX_PITCH)
+    }
+
+    pub fn tick(&mut self, seconds: f32) {
+        if self.streak == 0 {
+            return;
+        }
+
+        self.window -= seconds;
+        if self.window <= 0.0 {
+            self.streak = if self.multiplier() > 1 {
+                // back to the start of the level below
+                ((self.multiplier() - 2) * PICKUPS_PER_LEVEL) + 1
+            } else {
+                0
+            };
+            self.window = COMBO_WINDOW;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.streak = 0;
+        self.window = 0.0;
+    }
+}
+
+fn start_combo(mut combo: ResMut<Combo>) {
+    *combo = Combo::default();
+}
+
+fn update_combo(
+    mut combo: ResMut<Combo>,
+    mut player_state_events: EventReader<state::PlayerStateEvent>,
+    time: Res<Time>,
+) {
+    combo.tick(time.raw_delta_seconds());
+    if player_state_events.iter().any(|event| event.to == state::PlayerState::Stalled) {
+        combo.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn streak_of(pickups: usize) -> Combo {
+        let mut combo = Combo::default();
+        for _ in 0..pickups {
+            combo.collect();
+        }
+        combo
+    }
+
+    #[test]
+    fn multiplier_and_pitch_go_up_with_the_streak() {
+        let mut combo = Combo::default();
+        let multipliers = (0..20).map(|_| combo.collect()).collect::<Vec<_>>();
+        assert_eq!(&multipliers[..10], &[1, 1, 1, 2, 2, 2, 3, 3, 3, 4]);
+        assert_eq!(multipliers[19], MAX_MULTIPLIER);
+
+        assert_eq!(streak_of(1).pitch(), 1.0);
+        assert!((streak_of(3).pitch() - (1.0 + (2.0 * PITCH_STEP))).abs() < 1e-9);
+        assert_eq!(streak_of(100).pitch(), MAX_PITCH);
+    }
+
+    #[test]
+    fn running_out_of_window_drops_a_level() {
+        let mut combo = streak_of(8);
+        assert_eq!(combo.multiplier(), 3);
+
+        // still inside the window nothing changes
+        combo.tick(COMBO_WINDOW * 0.5);
+        assert_eq!(combo.streak, 8);
+
+        combo.tick(COMBO_WINDOW * 0.5);
+        assert_eq!((combo.streak, combo.multiplier()), (4, 2));
+        combo.tick(COMBO_WINDOW);
+        assert_eq!((combo.streak, combo.multiplier()), (1, 1));
+        combo.tick(COMBO_WINDOW);
+        assert_eq!(combo.streak, 0);
+        assert_eq!(combo.best, 8);
+    }
+
+    #[test]
+    fn stalling_ends_the_streak_but_keeps_the_best() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<state::PlayerStateEvent>()
+            .insert_resource(streak_of(5))
+            .add_system(update_combo);
+
+        app.world.send_event(state::PlayerStateEvent {
+            entity: Entity::from_raw(0),
+            from: state::PlayerState::Grounded,
+            to: state::PlayerState::Stalled,
+        });
+        app.update();
+
+        let combo = app.world.resource::<Combo>();
+        assert_eq!((combo.streak, combo.multiplier()), (0, 1));
+        assert_eq!(combo.best, 5);
+    }
+}
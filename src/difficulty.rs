@@ -0,0 +1,245 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use crate::{floor, player, powerup, AppState, RunArgs};
+
+pub static DIFFICULTY_PATH: &str = "difficulty/presets.difficulty.ron";
+
+pub struct DifficultyPlugin;
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        let difficulty = DifficultyProfile::from_run_args(app.world.resource::<RunArgs>());
+        app
+            .insert_resource(difficulty)
+            .add_asset::<DifficultyConfig>()
+            .init_asset_loader::<DifficultyConfigLoader>()
+            .add_system(update_difficulty.in_set(OnUpdate(AppState::InGame)));
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Preset {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Preset {
+    pub fn next(self) -> Self {
+        match self {
+            Preset::Easy => Preset::Normal,
+            Preset::Normal => Preset::Hard,
+            Preset::Hard => Preset::Easy,
+        }
+    }
+
+    pub fn previous(self) -> Self {
+        match self {
+            Preset::Easy => Preset::Hard,
+            Preset::Normal => Preset::Easy,
+            Preset::Hard => Preset::Normal,
+        }
+    }
+}
+
+// what the curves are measured against
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum RampBy {
+    // how far along the track the player is
+    #[default]
+    Distance,
+    // seconds since the run started
+    Time,
+}
+
+// points of (distance or time, value), straight lines in between and
+// flat past either end
+#[derive(Deserialize, Clone)]
+#[serde(transparent)]
+pub struct Curve {
+    points: Vec<(f32, f32)>,
+}
+
+impl Curve {
+    pub fn flat(value: f32) -> Self {
+        Curve { points: vec!((0.0, value)) }
+    }
+
+    pub fn ramp(from: (f32, f32), to: (f32, f32)) -> Self {
+        Curve { points: vec!(from, to) }
+    }
+
+    pub fn sample(&self, at: f32) -> f32 {
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return 0.0,
+        };
+
+        if at <= first.0 {
+            return first.1;
+        }
+
+        for pair in self.points.windows(2) {
+            let ((start, start_value), (end, end_value)) = (pair[0], pair[1]);
+            if at <= end {
+                let t = if end > start { (at - start) / (end - start) } else { 1.0 };
+                return start_value + ((end_value - start_value) * t);
+            }
+        }
+
+        last.1
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DifficultyCurves {
+    // how fast the player runs, used to be 20.0
+    pub player_speed: Curve,
+    // how much the track grows and the cutoff rises each lap, used to be 0.1
+    pub growth: Curve,
+    // how long the player can stand still before it's game over, used to be 3.0
+    pub stall_time: Curve,
+}
+
+impl DifficultyCurves {
+    pub fn preset(preset: Preset) -> Self {
+        match preset {
+            Preset::Easy => DifficultyCurves {
+                player_speed: Curve::ramp((0.0, 16.0), (1000.0, 20.0)),
+                growth: Curve::flat(0.05),
+                stall_time: Curve::flat(5.0),
+            },
+            Preset::Normal => DifficultyCurves {
+                player_speed: Curve::ramp((0.0, 20.0), (1000.0, 24.0)),
+                growth: Curve::flat(0.1),
+                stall_time: Curve::flat(3.0),
+            },
+            Preset::Hard => DifficultyCurves {
+                player_speed: Curve::ramp((0.0, 22.0), (800.0, 30.0)),
+                growth: Curve::ramp((0.0, 0.1), (800.0, 0.2)),
+                stall_time: Curve::ramp((0.0, 2.0), (800.0, 1.0)),
+            },
+        }
+    }
+}
+
+// the curves for every preset, loaded from DIFFICULTY_PATH so they
+// can be tuned without rebuilding
+#[derive(Deserialize, TypeUuid, Clone)]
+#[uuid = "0b6b2bd4-3c8e-4b1a-a2f4-6f3d1f0b9c27"]
+pub struct DifficultyConfig {
+    #[serde(default)]
+    pub ramp_by: RampBy,
+    pub easy: DifficultyCurves,
+    pub normal: DifficultyCurves,
+    pub hard: DifficultyCurves,
+}
+
+impl DifficultyConfig {
+    pub fn curves(&self, preset: Preset) -> &DifficultyCurves {
+        match preset {
+            Preset::Easy => &self.easy,
+            Preset::Normal => &self.normal,
+            Preset::Hard => &self.hard,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DifficultyConfigLoader;
+
+impl AssetLoader for DifficultyConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let config = ron::de::from_bytes::<DifficultyConfig>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["difficulty.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct DifficultyProfile {
+    pub preset: Preset,
+    pub ramp_by: RampBy,
+    pub curves: DifficultyCurves,
+    // distance or time into the run depending on ramp_by
+    pub progress: f32,
+}
+
+impl Default for DifficultyProfile {
+    fn default() -> Self {
+        DifficultyProfile {
+            preset: Preset::Normal,
+            ramp_by: RampBy::Distance,
+            curves: DifficultyCurves::preset(Preset::Normal),
+            progress: 0.0,
+        }
+    }
+}
+
+impl DifficultyProfile {
+    // starts on the preset picked with "--difficulty", if any
+    pub fn from_run_args(run_args: &RunArgs) -> Self {
+        let mut difficulty = DifficultyProfile::default();
+        if let Some(preset) = run_args.difficulty {
+            difficulty.preset = preset;
+        }
+
+        difficulty.curves = DifficultyCurves::preset(difficulty.preset);
+        difficulty
+    }
+
+    // takes the chosen preset's curves from the config if it loaded, otherwise
+    // the built in ones for that preset, a missing or broken file isn't fatal
+    pub fn start_run(&mut self, config: Option<&DifficultyConfig>) {
+        if let Some(config) = config {
+            self.ramp_by = config.ramp_by;
+            self.curves = config.curves(self.preset).clone();
+        } else {
+            self.ramp_by = RampBy::Distance;
+            self.curves = DifficultyCurves::preset(self.preset);
+        }
+        self.progress = 0.0;
+    }
+
+    pub fn player_speed(&self) -> f32 {
+        self.curves.player_speed.sample(self.progress)
+    }
+
+    pub fn growth(&self) -> f32 {
+        self.curves.growth.sample(self.progress)
+    }
+
+    pub fn stall_time(&self) -> f32 {
+        self.curves.stall_time.sample(self.progress)
+    }
+}
+
+pub fn update_difficulty(
+    mut difficulty: ResMut<DifficultyProfile>,
+    mut players: Query<(&mut player::Player, &Transform)>,
+    floor_manager: Res<floor::FloorManager>,
+    power_ups: Res<powerup::PowerUps>,
+    time: Res<Time>,
+) {
+    for (mut player, transform) in &mut players {
+        difficulty.progress = match difficulty.ramp_by {
+            RampBy::Distance => floor_manager.track_x(transform.translation.x).max(0.0),
+            RampBy::Time => difficulty.progress + time.delta_seconds(),
+        };
+
+        player.speed = difficulty.player_speed() * power_ups.speed_multiplier();
+    }
+}
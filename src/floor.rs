@@ -1,29 +1,52 @@
 use bevy::prelude::*;
+use bevy::render::primitives::Aabb;
 use bevy_rapier3d::prelude::*;
 use crate::{
+    difficulty,
     direction,
     food,
     player,
     AppState,
     ZeroSignum,
     CleanupMarker,
-    random_number,
+    rng,
+    RunArgs,
     FLOOR_COLOR,
 };
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-static FLOOR_CUBE_SIZE: f32 = 0.3;
-static GROUND_SPEED: f32 = 20.0;
-static NUMBER_OF_LIVE_ROWS: i32 = 100;
+pub mod heightmap;
+pub mod kind;
+pub mod level;
+pub mod noise;
+pub mod origin;
+pub mod palette;
+pub mod platform;
+pub mod shape;
+pub mod row_mesh;
+pub mod track;
+use track::TrackGenerator;
+
+pub static FLOOR_CUBE_SIZE: f32 = 0.3;
+pub static NUMBER_OF_LIVE_ROWS: i32 = 100;
 static NUMBER_OF_ROWS: i32 = 200;
-static NUMBER_OF_COLUMNS: i32 = 30;
-static DISTANCE_INCREASE: f32 = 0.1;
+pub static NUMBER_OF_COLUMNS: i32 = 30;
+static COLLIDER_DISTANCE: f32 = 2.0;
+// where the heightfield drops to under a missing block
+static HEIGHTFIELD_HOLE: f32 = -10.0;
+const TRACK_RNG_STREAM: u64 = 0x74_7261_636b;
 
 
 pub struct FloorPlugin;
 impl Plugin for FloorPlugin {
     fn build(&self, app: &mut App) {
+        let track_settings = track::TrackSettings::from_run_args(app.world.resource::<RunArgs>());
         app
+            .insert_resource(track_settings)
+            .add_asset::<level::Level>()
+            .init_asset_loader::<level::LevelLoader>()
+            .add_event::<level::LevelCompleteEvent>()
+            .init_resource::<palette::Palette>()
             .init_resource::<FloorManager>();
     }
 }
@@ -37,18 +60,105 @@ pub struct FloorManager {
     actual_lowest: f32,
     lowest: f32,
     highest: f32,
-    cube_mesh: Handle<Mesh>,
+    floor_material: Handle<StandardMaterial>,
     floor_spawn_cutoff: f32,
+    track_generator: Option<Box<dyn TrackGenerator>>,
+    track_rng: rng::RunRng,
+    generated_rows: i32,
+    endless: bool,
+    end_row: Option<i32>,
+    collider_mode: ColliderMode,
+    // the row sitting at x = 0 since the floating origin last moved
+    origin_row: i32,
+    // which spawned row and which of its blocks is at each (row, column)
+    floor_grid: HashMap<(i32, i32), (Entity, usize)>,
+    // how tall the block at each (row, column) is, kept alongside floor_grid
+    // so heights can be looked up without going through the chunks
+    floor_heights: HashMap<(i32, i32), f32>,
+    floor_colliders: HashMap<(i32, i32), Entity>,
+    // blocks that have been touched and how long they've got left
+    crumbling: HashMap<(i32, i32), f32>,
+    // blocks a ground pound has landed on since press_floors last ran
+    pounded: HashSet<(i32, i32)>,
+    // the first and one past the last column of every live row
+    row_bounds: HashMap<i32, (i32, i32)>,
+}
+
+// cuboids give each block near the player its own box, heightfield gives
+// every row one surface covering all its blocks
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum ColliderMode {
+    #[default]
+    Cuboids,
+    Heightfield,
 }
 
 impl FloorManager {
     pub fn current_level_size(&self) -> (Vec2, Vec2) {
-        let farthest_visible_x = self.track_distance as f32 * FLOOR_CUBE_SIZE;
+        let farthest_visible_x = self.row_x(self.track_distance);
         let start_x = farthest_visible_x - (NUMBER_OF_LIVE_ROWS as f32 * FLOOR_CUBE_SIZE);
         let end_x = start_x + (NUMBER_OF_ROWS as f32 * FLOOR_CUBE_SIZE);
-        let half_width = (NUMBER_OF_COLUMNS as f32 * FLOOR_CUBE_SIZE) / 2.0;
 
-        (Vec2::new(start_x, -half_width), Vec2::new(end_x, half_width))
+        // as wide as the widest row goes either way
+        let (left, right) = self.row_bounds
+            .values()
+            .fold((i32::MAX, i32::MIN), |(left, right), (first, end)| (left.min(*first), right.max(*end)));
+        let (left, right) = if left < right {
+            (left as f32 * FLOOR_CUBE_SIZE, right as f32 * FLOOR_CUBE_SIZE)
+        } else {
+            let half_width = (NUMBER_OF_COLUMNS as f32 * FLOOR_CUBE_SIZE) / 2.0;
+            (-half_width, half_width)
+        };
+
+        (Vec2::new(start_x, left), Vec2::new(end_x, right))
+    }
+
+    // from the first live row to the last one spawned, where there's
+    // actually floor to put things on
+    pub fn spawned_size(&self) -> (f32, f32) {
+        let first_live_row = self.track_distance - NUMBER_OF_LIVE_ROWS;
+        (self.row_x(first_live_row), self.row_x(self.track_distance - 1))
+    }
+
+    // the edges of whichever row is at x, or the usual edges if there isn't one
+    pub fn row_bounds(&self, x: f32) -> (f32, f32) {
+        let row = (x / FLOOR_CUBE_SIZE).round() as i32 + self.origin_row;
+        let (first, end) = self.row_bounds
+            .get(&row)
+            .copied()
+            .unwrap_or((-NUMBER_OF_COLUMNS / 2, NUMBER_OF_COLUMNS / 2));
+        (first as f32 * FLOOR_CUBE_SIZE, end as f32 * FLOOR_CUBE_SIZE)
+    }
+
+    // the middle of the track at x
+    pub fn center_z(&self, x: f32) -> f32 {
+        let (left, right) = self.row_bounds(x);
+        (left + right) / 2.0
+    }
+
+    // the top of the block under (x, z), None over a gap or off the track.
+    // moving platforms aren't counted
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let row = (x / FLOOR_CUBE_SIZE).round() as i32 + self.origin_row;
+        let column = (z / FLOOR_CUBE_SIZE).round() as i32;
+        self.floor_heights
+            .get(&(row, column))
+            .map(|height| height * FLOOR_CUBE_SIZE / 2.0)
+    }
+
+    // the z and the top of every block in the row at x, from left to right
+    pub fn column_profile(&self, x: f32) -> Vec<(f32, f32)> {
+        let row = (x / FLOOR_CUBE_SIZE).round() as i32 + self.origin_row;
+        let (first, end) = self.row_bounds
+            .get(&row)
+            .copied()
+            .unwrap_or((-NUMBER_OF_COLUMNS / 2, NUMBER_OF_COLUMNS / 2));
+        (first..end)
+            .filter_map(|column| {
+                let height = self.floor_heights.get(&(row, column))?;
+                Some((column as f32 * FLOOR_CUBE_SIZE, height * FLOOR_CUBE_SIZE / 2.0))
+            })
+            .collect()
     }
 
     pub fn current_level_heights(&self) -> (f32, f32) {
@@ -58,160 +168,364 @@ impl FloorManager {
     pub fn get_actual_lowest(&self) -> f32 {
         self.actual_lowest * FLOOR_CUBE_SIZE
     }
+
+    // where the finish line is, if the track has one
+    pub fn end_x(&self) -> Option<f32> {
+        self.end_row.map(|end_row| self.row_x(end_row))
+    }
+
+    // how far along the track an x really is, everything gets moved
+    // back towards zero now and again so translations stay small
+    pub fn track_x(&self, x: f32) -> f32 {
+        x + (self.origin_row as f32 * FLOOR_CUBE_SIZE)
+    }
+
+    // the other way around, from along the track to where it is now
+    pub fn world_x(&self, track_x: f32) -> f32 {
+        track_x - (self.origin_row as f32 * FLOOR_CUBE_SIZE)
+    }
+
+    pub fn start_crumbling(&mut self, cell: (i32, i32)) {
+        self.crumbling.entry(cell).or_insert(kind::CRUMBLE_TIME);
+    }
+
+    // crumbling blocks go straight away, the rest get pushed down
+    pub fn pound(&mut self, cell: (i32, i32), floor_kind: kind::FloorKind) {
+        if floor_kind == kind::FloorKind::Crumbling {
+            self.crumbling.insert(cell, 0.0);
+        } else {
+            self.pounded.insert(cell);
+        }
+    }
+
+    fn row_x(&self, row: i32) -> f32 {
+        (row - self.origin_row) as f32 * FLOOR_CUBE_SIZE
+    }
+
+    fn next_row(&mut self) -> FloorRow {
+        let row = self.generated_rows;
+        self.generated_rows += 1;
+
+        let track_generator = self.track_generator.get_or_insert_with(|| Box::new(track::FlatTrack));
+        track_generator.next_row(row, &mut self.track_rng)
+    }
 }
 
-#[derive(Default)]
 pub struct FloorRow {
     blocks: VecDeque::<Floor>,
+    // column and height of any donuts placed over this row
+    donuts: Vec<(f32, f32)>,
+    // how many columns across and how far the middle is from z = 0
+    width: i32,
+    offset: i32,
 }
 
-#[derive(Component, Reflect, Default, Clone, Copy)]
-#[reflect(Component)]
+impl Default for FloorRow {
+    fn default() -> Self {
+        FloorRow {
+            blocks: VecDeque::new(),
+            donuts: vec!(),
+            width: NUMBER_OF_COLUMNS,
+            offset: 0,
+        }
+    }
+}
+
+impl FloorRow {
+    // builds a full-width row, None leaves a hole at that column
+    pub fn from_heights(mut height_at: impl FnMut(i32) -> Option<f32>) -> Self {
+        let columns = NUMBER_OF_COLUMNS / 2;
+        let mut floor_row = FloorRow::default();
+        for z in -columns..columns {
+            if let Some(height) = height_at(z) {
+                floor_row.blocks.push_front(Floor {
+                    height,
+                    base: height,
+                    z: z as f32,
+                    row_id: 0,
+                    color: Color::hex(FLOOR_COLOR).unwrap(),
+                    kind: kind::FloorKind::Normal,
+                    motion: None,
+                });
+            }
+        }
+
+        floor_row
+    }
+
+    // carries over whatever grew on the recycled blocks on top of the freshly
+    // generated heights. anything that had dropped out comes back fresh and
+    // is left to the spawn cutoff
+    fn regrow(mut self, recycled: &[Floor]) -> Self {
+        for block in self.blocks.iter_mut() {
+            if let Some(old) = recycled.iter().find(|old| old.z == block.z) {
+                block.height = block.base + (old.height - old.base);
+                if old.kind == kind::FloorKind::Normal {
+                    block.color = old.color;
+                }
+            }
+        }
+
+        self
+    }
+}
+
+#[derive(Reflect, Default, Clone, Copy)]
 pub struct Floor {
     pub height: f32,
+    pub base: f32,
     pub row_id: usize,
     pub z: f32,
     pub color: Color,
+    pub kind: kind::FloorKind,
+    // moving blocks get pulled out of the row into a platform
+    #[reflect(ignore)]
+    pub motion: Option<platform::PlatformMotion>,
+}
+
+// one of these per spawned row, drawn as a single mesh
+#[derive(Component)]
+pub struct FloorChunk {
+    pub row: i32,
+    pub blocks: Vec<Floor>,
+    pub lowest: f32,
 }
 
+#[derive(Component)]
+pub struct FloorCollider {
+    pub row: i32,
+    pub z: f32,
+}
 
 pub fn setup_floor(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
+    palette: &mut palette::Palette,
     floor_manager: &mut ResMut<FloorManager>,
+    run_rng: &mut rng::RunRng,
+    track_generator: Box<dyn TrackGenerator>,
+    collider_mode: ColliderMode,
+    difficulty: &difficulty::DifficultyProfile,
     food_spawn_event_writer: &mut EventWriter<food::SpawnFoodEvent>,
 ) { 
     **floor_manager = FloorManager::default();
-    let columns = NUMBER_OF_COLUMNS / 2;
-    floor_manager.cube_mesh = meshes.add(Mesh::from(shape::Cube { size: FLOOR_CUBE_SIZE }));
+    floor_manager.collider_mode = collider_mode;
+    // block colors come from the row meshes' vertex colors
+    floor_manager.floor_material = palette.material(Color::WHITE, materials);
     floor_manager.lowest = 2.0; 
+    floor_manager.endless = track_generator.is_endless();
+    floor_manager.end_row = track_generator.end_row();
+    floor_manager.track_generator = Some(track_generator);
+    floor_manager.track_rng = run_rng.stream(TRACK_RNG_STREAM);
 
-    let mut row_id: usize = 0;
     for _ in 0..NUMBER_OF_ROWS {
-        let mut floor_row = FloorRow::default();
-        for z in -columns..columns {
-            let c = Color::hex(FLOOR_COLOR).unwrap();// * Vec3::new(color_x, color_x, color_x);
-
-            floor_row.blocks.push_front( Floor { 
-                height: 2.0, 
-                z: z as f32, 
-                row_id, 
-                color: c 
-            });
-        }
-
-        row_id += 1;
-        floor_manager.floor_rows.push_front(floor_row);
+        let floor_row = floor_manager.next_row();
+        floor_manager.floor_rows.push_back(floor_row);
     }
 
-    spawn_floors(commands, meshes, materials, NUMBER_OF_LIVE_ROWS, floor_manager, food_spawn_event_writer);
+    spawn_floors(commands, meshes, NUMBER_OF_LIVE_ROWS, floor_manager, run_rng, difficulty, food_spawn_event_writer);
 }
 
 pub fn update_floors(
     mut commands: Commands,
-    time: Res<Time>,
-    mut floors: Query<(Entity, &mut Floor, &Transform, &Children)>,
-    players: Query<(&Transform, &Velocity, &player::Player), Without<Floor>>,
-    mut transforms: Query<&mut Transform, (Without<player::Player>, Without<Floor>)>,
+    mut chunks: Query<(&mut FloorChunk, &Handle<Mesh>)>,
+    players: Query<(&Transform, &player::Player), Without<FloorChunk>>,
+    mut meshes: ResMut<Assets<Mesh>>,
     mut floor_manager: ResMut<FloorManager>,
 ) {
-    for (p, p_velocity, player) in &players {
-        if p_velocity.linvel.x < player.speed * 0.1 {
+    for (p, player) in &players {
+        if !player.state.is_moving() {
             return;
         }
 
-        let mut actual_lowest = floor_manager.highest;
-        for (entity, mut floor, transform, children) in &mut floors {
-            actual_lowest = actual_lowest.min(floor.height);
-            let player_translation = Vec3::new(p.translation.x, 0.0, p.translation.z);
-            let floor_translation = Vec3::new(transform.translation.x, 0.0, transform.translation.z);
-            let distance = (floor_translation - player_translation).length();
-            if distance > 2.0 || floor_translation.x <= player_translation.x {
-                commands.entity(entity)
-                        .remove::<RigidBody>()
-                        .remove::<Collider>();
-            } 
-            if distance <= 2.0 && floor_translation.x > player_translation.x {
-               let half_size = FLOOR_CUBE_SIZE / 2.0;
-               commands.entity(entity)
-                   .insert((RigidBody::Fixed,Collider::cuboid(half_size, half_size * floor.height, half_size)));
-            }
-
-            if distance < 0.5 && floor_translation.x < (player_translation.x - (FLOOR_CUBE_SIZE / 2.0)) {
-                floor.height += FLOOR_CUBE_SIZE * 2.0;// * time.delta_seconds();
-
-                for child_entity in children {
-                    if let Ok(mut child_transform) = transforms.get_mut(*child_entity) {
-                        child_transform.scale.y = floor.height;
+        // only the cells around the player get looked at
+        let player_translation = Vec3::new(p.translation.x, 0.0, p.translation.z);
+        let player_row = (p.translation.x / FLOOR_CUBE_SIZE).round() as i32 + floor_manager.origin_row;
+        let player_column = (p.translation.z / FLOOR_CUBE_SIZE).round() as i32;
+        let reach = (COLLIDER_DISTANCE / FLOOR_CUBE_SIZE).ceil() as i32;
+
+        let mut nearby = HashSet::new();
+        let mut grown = HashSet::new();
+        for row in (player_row - reach)..=(player_row + reach) {
+            for column in (player_column - reach)..=(player_column + reach) {
+                if let Some(&(chunk_entity, index)) = floor_manager.floor_grid.get(&(row, column)) {
+                    let floor_translation = Vec3::new(floor_manager.row_x(row), 0.0, column as f32 * FLOOR_CUBE_SIZE);
+                    let distance = (floor_translation - player_translation).length();
+                    if floor_manager.collider_mode == ColliderMode::Cuboids
+                    && distance <= COLLIDER_DISTANCE && floor_translation.x > player_translation.x {
+                        nearby.insert((row, column));
+                    }
+
+                    if distance < 0.5 && floor_translation.x < (player_translation.x - (FLOOR_CUBE_SIZE / 2.0)) {
+                        if let Ok((mut chunk, _)) = chunks.get_mut(chunk_entity) {
+                            chunk.blocks[index].height += FLOOR_CUBE_SIZE * 2.0;
+                            floor_manager.floor_heights.insert((row, column), chunk.blocks[index].height);
+                            grown.insert(chunk_entity);
+                        }
                     }
                 }
             }
         }
 
-        floor_manager.actual_lowest = actual_lowest;
+        // colliders only come and go as blocks enter and leave the neighbourhood
+        floor_manager.floor_colliders.retain(|cell, collider| {
+            if nearby.contains(cell) {
+                true
+            } else {
+                commands.entity(*collider).despawn_recursive();
+                false
+            }
+        });
+
+        for (row, column) in nearby {
+            if floor_manager.floor_colliders.contains_key(&(row, column)) {
+                continue;
+            }
+
+            let (chunk_entity, index) = floor_manager.floor_grid[&(row, column)];
+            if let Ok((chunk, _)) = chunks.get(chunk_entity) {
+                let floor = chunk.blocks[index];
+                let half_size = FLOOR_CUBE_SIZE / 2.0;
+                let collider = commands.spawn((
+                    TransformBundle::from(Transform::from_xyz(floor_manager.row_x(row), 0.0, floor.z * FLOOR_CUBE_SIZE)),
+                    RigidBody::Fixed,
+                    Collider::cuboid(half_size, half_size * floor.height, half_size),
+                    FloorCollider { row, z: floor.z },
+                    CleanupMarker,
+                )).id();
+                floor_manager.floor_colliders.insert((row, column), collider);
+            }
+        }
+
+        for chunk_entity in grown {
+            if let Ok((mut chunk, mesh)) = chunks.get_mut(chunk_entity) {
+                chunk.lowest = chunk.blocks.iter().map(|floor| floor.height).fold(f32::MAX, f32::min);
+                if let Some(mesh) = meshes.get_mut(mesh) {
+                    *mesh = row_mesh::build_row_mesh(&chunk.blocks);
+                }
+                if floor_manager.collider_mode == ColliderMode::Heightfield {
+                    commands.entity(chunk_entity).insert(heightfield_collider(&chunk.blocks));
+                }
+            }
+            // the bounds are only worked out once so make them get worked out again
+            commands.entity(chunk_entity).remove::<Aabb>();
+        }
+
+        floor_manager.actual_lowest = chunks
+            .iter()
+            .map(|(chunk, _)| chunk.lowest)
+            .fold(floor_manager.highest, f32::min);
     }
 }
 
 pub fn shift_floors(
     mut commands: Commands,
-    mut floors: Query<(Entity, &Floor, &Transform)>,
+    chunks: Query<(Entity, &FloorChunk, &Transform)>,
     cameras: Query<&Transform, With<Camera3d>>,
     mut floor_manager: ResMut<FloorManager>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut run_rng: ResMut<rng::RunRng>,
+    difficulty: Res<difficulty::DifficultyProfile>,
+    platforms: Query<(Entity, &platform::Platform)>,
     mut food_spawn_event_writer: EventWriter<food::SpawnFoodEvent>,
 ) {
     let mut rows = HashMap::<usize, Vec::<Floor>>::new();
-    for (entity, floor, transform) in &mut floors {
+    for (entity, chunk, transform) in &chunks {
         for camera in &cameras {
             if transform.translation.x < camera.translation.x {
-                let floor = floor.clone();
-                rows.entry(floor.row_id).and_modify(|r| r.push(floor)).or_insert(vec!(floor));
+                for floor in chunk.blocks.iter() {
+                    let cell = (chunk.row, floor.z as i32);
+                    floor_manager.floor_grid.remove(&cell);
+                    floor_manager.floor_heights.remove(&cell);
+                    if let Some(collider) = floor_manager.floor_colliders.remove(&cell) {
+                        commands.entity(collider).despawn_recursive();
+                    }
+                }
+                rows.insert(chunk.row as usize, chunk.blocks.clone());
                 commands.entity(entity).despawn_recursive();
             }
         }
     }
 
-    if !rows.is_empty() {
-        let number_of_rows_to_add = rows.len() as i32;
-        // does this need to be sorted?
-        for (_, mut floors) in rows.drain() {
-            floors.sort_by(|a, b| a.z.partial_cmp(&b.z).unwrap());
-            let row = FloorRow {
-                blocks: floors.into()
-            };
+    // every row the camera has gone past gets swapped for a new one at the far end,
+    // empty ones included so gaps in the track don't eat into how far ahead we can see
+    let camera_x = cameras.iter().map(|camera| camera.translation.x).fold(f32::MIN, f32::max);
+
+    // platforms get generated fresh with their row so they just go
+    for (entity, platform) in &platforms {
+        if floor_manager.row_x(platform.row) < camera_x {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    let first_live_row = floor_manager.track_distance - NUMBER_OF_LIVE_ROWS;
+    let camera_row = (camera_x / FLOOR_CUBE_SIZE).ceil() as i32 + floor_manager.origin_row;
+    let number_of_rows_to_add = (camera_row - first_live_row).max(0);
 
+    if number_of_rows_to_add > 0 {
+        let new_first_live_row = first_live_row + number_of_rows_to_add;
+        floor_manager.row_bounds.retain(|row, _| *row >= new_first_live_row);
+
+        for x in first_live_row..(first_live_row + number_of_rows_to_add) {
+            let recycled = rows.remove(&(x as usize)).unwrap_or_default();
+            let row = floor_manager.next_row().regrow(&recycled);
             floor_manager.floor_rows.push_back(row);
         }
 
-        spawn_floors(&mut commands, &mut meshes, &mut materials, number_of_rows_to_add, &mut floor_manager, &mut food_spawn_event_writer);
+        spawn_floors(&mut commands, &mut meshes, number_of_rows_to_add, &mut floor_manager, &mut run_rng, &difficulty, &mut food_spawn_event_writer);
     }
 }
 
 fn spawn_floors(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<StandardMaterial>>,
     number_of_rows_to_spawn: i32,
     floor_manager: &mut FloorManager,
+    run_rng: &mut rng::RunRng,
+    difficulty: &difficulty::DifficultyProfile,
     food_spawn_event_writer: &mut EventWriter<food::SpawnFoodEvent>,
 ) {
+    let growth = difficulty.growth();
     for x in floor_manager.track_distance..(floor_manager.track_distance + number_of_rows_to_spawn) {
         if let Some(mut floor_row) = floor_manager.floor_rows.pop_front() {
-            // reset the lowest when we run into the first row
-            if let Some(front) = floor_row.blocks.front() {
-                if front.row_id == 0 {
-                    food_spawn_event_writer.send(food::SpawnFoodEvent);
-                    floor_manager.lowest = f32::MAX;
-                    floor_manager.highest = f32::MIN;
-                    floor_manager.floor_spawn_cutoff += DISTANCE_INCREASE + (FLOOR_CUBE_SIZE * 2.0);
-                    println!("{} {} {}", floor_manager.actual_lowest, floor_manager.lowest, floor_manager.floor_spawn_cutoff);
+            // reset the lowest every time we come back around to the first row.
+            // counted by distance since a generated row can be empty
+            if x % NUMBER_OF_ROWS == 0 {
+                floor_manager.lowest = f32::MAX;
+                floor_manager.highest = f32::MIN;
+
+                // handcrafted tracks place their own donuts and stay the height they were made
+                if floor_manager.endless {
+                    food_spawn_event_writer.send(food::SpawnFoodEvent {
+                        position: None,
+                        pattern: food::pattern::DonutPattern::random(run_rng),
+                    });
+                    floor_manager.floor_spawn_cutoff += growth + (FLOOR_CUBE_SIZE * 2.0);
                 }
+                debug!("{} {} {}", floor_manager.actual_lowest, floor_manager.lowest, floor_manager.floor_spawn_cutoff);
+            }
+
+            let first_column = floor_row.offset - (floor_row.width / 2);
+            floor_manager.row_bounds.insert(x, (first_column, first_column + floor_row.width));
+
+            for (z, height) in floor_row.donuts.drain(..) {
+                food_spawn_event_writer.send(food::SpawnFoodEvent {
+                    position: Some(Vec3::new(
+                        x as f32 * FLOOR_CUBE_SIZE,
+                        (height * FLOOR_CUBE_SIZE * 0.5) + 0.5,
+                        z * FLOOR_CUBE_SIZE,
+                    )),
+                    ..default()
+                });
             }
 
+            let mut blocks = vec!();
             while let Some(mut block) = floor_row.blocks.pop_front() {
-                block.height *= 1.0 + DISTANCE_INCREASE;
+                block.row_id = x as usize;
+                if floor_manager.endless {
+                    block.height *= 1.0 + growth;
+                }
                 floor_manager.lowest = floor_manager.lowest.min(block.height);
                 floor_manager.highest = floor_manager.highest.max(block.height);
 
@@ -220,35 +534,299 @@ fn spawn_floors(
                     continue;
                 }
                 
-                let color_x = random_number();//* block.height;
-                block.color.set_g(block.color.g() - (color_x * 0.1));
-                block.color.set_r(block.color.r() - (color_x * 0.1));
-                block.color.set_b(block.color.b() - (color_x * 0.1));
-                commands.spawn((
-                TransformBundle::from(Transform::from_xyz(x as f32 * FLOOR_CUBE_SIZE, 0.0, block.z as f32 * FLOOR_CUBE_SIZE)),
-                block,
-                ComputedVisibility::default(),
-                Visibility::Visible,
+                if let Some(color) = palette::Palette::kind_color(block.kind) {
+                    block.color = color;
+                } else {
+                    let color_x = run_rng.random_number();//* block.height;
+                    block.color.set_g(block.color.g() - (color_x * 0.1));
+                    block.color.set_r(block.color.r() - (color_x * 0.1));
+                    block.color.set_b(block.color.b() - (color_x * 0.1));
+                    block.color = palette::Palette::quantize(block.color);
+                }
+                blocks.push(block);
+            }
+
+            let (moving, blocks): (Vec<Floor>, Vec<Floor>) = blocks.into_iter().partition(|block| block.motion.is_some());
+            if !moving.is_empty() {
+                platform::spawn_platform(commands, meshes, floor_manager, x, &moving);
+            }
+
+            if blocks.is_empty() {
+                continue;
+            }
+
+            let columns = blocks.iter().map(|floor| (floor.z as i32, floor.height)).collect::<Vec<_>>();
+            let lowest = blocks.iter().map(|floor| floor.height).fold(f32::MAX, f32::min);
+            let collider = if floor_manager.collider_mode == ColliderMode::Heightfield {
+                Some((RigidBody::Fixed, heightfield_collider(&blocks)))
+            } else {
+                None
+            };
+            let mut chunk_commands = commands.spawn((
+                PbrBundle {
+                    mesh: meshes.add(row_mesh::build_row_mesh(&blocks)),
+                    material: floor_manager.floor_material.clone(),
+                    transform: Transform::from_xyz(floor_manager.row_x(x), 0.0, 0.0),
+                    ..default()
+                },
+                FloorChunk { row: x, blocks, lowest },
                 CleanupMarker,
-    //          RigidBody::Fixed,
-    //          Collider::cuboid(0.1, 0.1, 0.1)
-                )).with_children(|parent| {
-                    parent.spawn((
-                        PbrBundle {
-                            mesh: floor_manager.cube_mesh.clone_weak(),
-                            material: materials.add(StandardMaterial {
-                                base_color: block.color.into(),
-                                unlit: false,
-                                ..default()
-                            }),
-                            transform: Transform::from_scale(Vec3::new(1.0, block.height , 1.0)),
-                            ..default()
-                        }
-                    ,));
-                });
+            ));
+            if let Some(collider) = collider {
+                chunk_commands.insert(collider);
+            }
+            let chunk_entity = chunk_commands.id();
+
+            for (index, (column, height)) in columns.into_iter().enumerate() {
+                floor_manager.floor_grid.insert((x, column), (chunk_entity, index));
+                floor_manager.floor_heights.insert((x, column), height);
             }
         }
     }
 
     floor_manager.track_distance += number_of_rows_to_spawn;
 }
+
+// one sample per column along z and the same heights at both edges of the row
+// along x, so the player runs over the tops of the blocks as one surface
+fn heightfield_collider(blocks: &[Floor]) -> Collider {
+    // the heightfield sits centered on the row so it reaches as far
+    // both ways as the row does one way
+    let columns = blocks
+        .iter()
+        .map(|block| block.z.abs() as i32 + 1)
+        .max()
+        .unwrap_or(1);
+    let number_of_samples = ((columns * 2) + 1) as usize;
+    let mut heights = vec![HEIGHTFIELD_HOLE; number_of_samples];
+    for block in blocks {
+        heights[(block.z as i32 + columns) as usize] = block.height * FLOOR_CUBE_SIZE / 2.0;
+    }
+
+    // column-major, both edges of the row get the same heights
+    let heights = [heights.clone(), heights].concat();
+    Collider::heightfield(
+        heights,
+        number_of_samples,
+        2,
+        Vec3::new(FLOOR_CUBE_SIZE, 1.0, (columns * 2) as f32 * FLOOR_CUBE_SIZE),
+    )
+}
+
+// a floor straight from the height of each (row, column), with nothing
+// spawned, for checking things that only look the floor up
+#[cfg(test)]
+impl FloorManager {
+    pub fn from_heights(heights: impl IntoIterator<Item = ((i32, i32), f32)>) -> Self {
+        FloorManager {
+            floor_heights: heights.into_iter().collect(),
+            ..default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+    use super::*;
+    use crate::player::ability;
+
+    static TEST_SEED: u64 = 1234;
+    // about a third of a row every frame, like the bench
+    static TEST_STEP: f32 = 0.1;
+
+    // the floor on its own with a player to grow it and a camera to recycle it
+    fn floor_app(collider_mode: ColliderMode) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .add_event::<food::SpawnFoodEvent>()
+            .insert_resource(rng::RunRng::from_seed(TEST_SEED))
+            .insert_resource(track::TrackSettings { collider_mode, ..default() })
+            .init_resource::<palette::Palette>()
+            .init_resource::<difficulty::DifficultyProfile>()
+            .init_resource::<FloorManager>()
+            .add_startup_system(setup)
+            .add_systems((
+                    update_floors,
+                    kind::press_floors,
+                    kind::crumble_floors,
+                    shift_floors,
+                ).chain()
+            );
+        app.update();
+        app
+    }
+
+    fn setup(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        mut palette: ResMut<palette::Palette>,
+        mut floor_manager: ResMut<FloorManager>,
+        mut run_rng: ResMut<rng::RunRng>,
+        track_settings: Res<track::TrackSettings>,
+        difficulty: Res<difficulty::DifficultyProfile>,
+        mut food_spawn_event_writer: EventWriter<food::SpawnFoodEvent>,
+    ) {
+        let track_generator = Box::new(track::SegmentTrack::new(track::DEFAULT_SEGMENT_WEIGHTS.to_vec()));
+        setup_floor(&mut commands, &mut meshes, &mut materials, &mut palette, &mut floor_manager, &mut run_rng, track_generator, track_settings.collider_mode, &difficulty, &mut food_spawn_event_writer);
+
+        let player = player::Player::new(&mut run_rng);
+        commands.spawn((TransformBundle::from(Transform::from_xyz(0.0, 0.5, 0.0)), player));
+        commands.spawn((TransformBundle::from(Transform::from_xyz(-2.8, 1.0, 0.0)), Camera3d::default()));
+    }
+
+    // weaves the player across the track so blocks keep growing behind it
+    fn walk(app: &mut App, frames: u32) {
+        for _ in 0..frames {
+            let world = &mut app.world;
+            let mut player = world.query_filtered::<&mut Transform, With<player::Player>>().single_mut(world);
+            player.translation.x += TEST_STEP;
+            player.translation.z = (player.translation.x * 0.5).sin() * 3.0;
+            let player_x = player.translation.x;
+            world.query_filtered::<&mut Transform, With<Camera3d>>().single_mut(world).translation.x = player_x - 2.8;
+            app.update();
+        }
+    }
+
+    fn top(floor: &Floor) -> f32 {
+        floor.height * FLOOR_CUBE_SIZE / 2.0
+    }
+
+    // height_at, the row meshes and the colliders all have to agree on every block
+    fn check_heights(world: &mut World) {
+        let rows = world
+            .query::<(&FloorChunk, &Transform, &Handle<Mesh>, Option<&Collider>)>()
+            .iter(world)
+            .map(|(chunk, transform, mesh, collider)| (chunk.blocks.clone(), transform.translation.x, mesh.clone(), collider.cloned()))
+            .collect::<Vec<_>>();
+        let colliders = world
+            .query::<(&FloorCollider, &Collider, &Transform)>()
+            .iter(world)
+            .map(|(floor_collider, collider, transform)| ((floor_collider.row, floor_collider.z), collider.clone(), transform.translation))
+            .collect::<Vec<_>>();
+        let floor_manager = world.resource::<FloorManager>();
+        let meshes = world.resource::<Assets<Mesh>>();
+        assert!(!rows.is_empty());
+
+        for (blocks, x, mesh, collider) in rows {
+            assert_eq!(floor_manager.column_profile(x).len(), blocks.len(), "row at {} has heights left over", x);
+
+            let positions = match meshes.get(&mesh).and_then(|mesh| mesh.attribute(Mesh::ATTRIBUTE_POSITION)) {
+                Some(VertexAttributeValues::Float32x3(positions)) => positions.clone(),
+                _ => panic!("row at {} has no mesh", x),
+            };
+            let vertices_per_block = positions.len() / blocks.len().max(1);
+            assert_eq!(positions.len(), blocks.len() * vertices_per_block);
+
+            for (index, block) in blocks.iter().enumerate() {
+                let z = block.z * FLOOR_CUBE_SIZE;
+                assert_eq!(floor_manager.height_at(x, z), Some(top(block)), "height_at({}, {})", x, z);
+
+                let block_vertices = &positions[(index * vertices_per_block)..((index + 1) * vertices_per_block)];
+                let mesh_top = block_vertices.iter().map(|position| position[1]).fold(f32::MIN, f32::max);
+                assert!((mesh_top - top(block)).abs() < 0.0001, "mesh top {} for a block {} tall", mesh_top, top(block));
+            }
+
+            if let Some(heights) = collider.as_ref().and_then(|collider| collider.as_heightfield()).map(|view| view.heights().to_vec()) {
+                let columns = (heights.len() / 2 - 1) as i32 / 2;
+                for block in &blocks {
+                    assert_eq!(heights[(block.z as i32 + columns) as usize], top(block));
+                }
+            }
+        }
+
+        for ((row, column), collider, translation) in colliders {
+            let half_extents = collider.as_cuboid().expect("floor colliders are cuboids").half_extents();
+            // centered on y = 0 so the top is half the height
+            assert_eq!(floor_manager.height_at(translation.x, translation.z), Some(half_extents.y), "collider at row {} column {}", row, column);
+        }
+    }
+
+    // the nearest block ahead of the player that has a collider
+    fn block_ahead(world: &mut World) -> (i32, i32) {
+        let player_x = world.query_filtered::<&Transform, With<player::Player>>().single(world).translation.x;
+        world
+            .query::<(&FloorCollider, &Transform)>()
+            .iter(world)
+            .filter(|(_, transform)| transform.translation.x > player_x)
+            .min_by(|(_, a), (_, b)| a.translation.x.partial_cmp(&b.translation.x).unwrap())
+            .map(|(floor_collider, _)| (floor_collider.row, floor_collider.z as i32))
+            .expect("no floor ahead of the player")
+    }
+
+    fn cell_height(world: &World, cell: (i32, i32)) -> Option<f32> {
+        let floor_manager = world.resource::<FloorManager>();
+        floor_manager.height_at(floor_manager.row_x(cell.0), cell.1 as f32 * FLOOR_CUBE_SIZE)
+    }
+
+    #[test]
+    fn heights_follow_shifted_and_grown_rows() {
+        let mut app = floor_app(ColliderMode::Cuboids);
+        check_heights(&mut app.world);
+        // a few hundred rows, so every row gets recycled and regrown
+        for _ in 0..10 {
+            walk(&mut app, 100);
+            check_heights(&mut app.world);
+        }
+
+        let grown = app.world
+            .query::<&FloorChunk>()
+            .iter(&app.world)
+            .flat_map(|chunk| chunk.blocks.iter())
+            .any(|block| block.height > block.base);
+        assert!(grown, "nothing grew so nothing was checked");
+    }
+
+    #[test]
+    fn heights_follow_heightfield_rows() {
+        let mut app = floor_app(ColliderMode::Heightfield);
+        for _ in 0..10 {
+            walk(&mut app, 100);
+            check_heights(&mut app.world);
+        }
+    }
+
+    #[test]
+    fn pounded_blocks_go_down_everywhere() {
+        let mut app = floor_app(ColliderMode::Cuboids);
+        walk(&mut app, 50);
+        let cell = block_ahead(&mut app.world);
+        let before = cell_height(&app.world, cell).unwrap();
+
+        app.world.resource_mut::<FloorManager>().pound(cell, kind::FloorKind::Normal);
+        app.update();
+
+        let pressed = ((before / FLOOR_CUBE_SIZE * 2.0) - ability::POUND_DEPTH).max(kind::MIN_POUNDED_HEIGHT);
+        assert_eq!(cell_height(&app.world, cell), Some(pressed * FLOOR_CUBE_SIZE / 2.0));
+        check_heights(&mut app.world);
+    }
+
+    #[test]
+    fn crumbled_blocks_are_gone_everywhere() {
+        let mut app = floor_app(ColliderMode::Cuboids);
+        walk(&mut app, 50);
+        let cell = block_ahead(&mut app.world);
+
+        app.world.resource_mut::<FloorManager>().pound(cell, kind::FloorKind::Crumbling);
+        app.update();
+
+        assert_eq!(cell_height(&app.world, cell), None);
+        let (row, column) = cell;
+        let left_behind = app.world
+            .query::<&FloorChunk>()
+            .iter(&app.world)
+            .filter(|chunk| chunk.row == row)
+            .any(|chunk| chunk.blocks.iter().any(|block| block.z as i32 == column));
+        assert!(!left_behind, "the block is still in its row");
+        let collider_left = app.world
+            .query::<&FloorCollider>()
+            .iter(&app.world)
+            .any(|floor_collider| floor_collider.row == row && floor_collider.z as i32 == column);
+        assert!(!collider_left, "the block's collider is still there");
+        check_heights(&mut app.world);
+    }
+}
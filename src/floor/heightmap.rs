@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use crate::rng;
+use super::{track::TrackGenerator, FloorRow, NUMBER_OF_COLUMNS};
+
+#[derive(Clone)]
+pub struct HeightmapSettings {
+    pub path: String,
+    pub min_height: f32,
+    pub max_height: f32,
+}
+
+impl Default for HeightmapSettings {
+    fn default() -> Self {
+        HeightmapSettings {
+            path: "tracks/example.png".to_string(),
+            min_height: 1.0,
+            max_height: 12.0,
+        }
+    }
+}
+
+// a grayscale image turned into a track. the bottom row of the image is
+// the start of the track, each pixel across a row is a block and black
+// pixels are holes. the image repeats once the track runs past the top
+pub struct HeightmapTrack {
+    rows: Vec<Vec<Option<f32>>>,
+}
+
+impl HeightmapTrack {
+    pub fn from_image(image: &Image, settings: &HeightmapSettings) -> Self {
+        let width = image.texture_descriptor.size.width as i32;
+        let height = image.texture_descriptor.size.height as i32;
+        let columns = NUMBER_OF_COLUMNS / 2;
+
+        let rows = (0..height)
+            .rev()
+            .map(|y| {
+                // pixels are lined up from the middle of the image out
+                (-columns..columns)
+                    .map(|z| {
+                        let x = z + (width / 2);
+                        if !(0..width).contains(&x) {
+                            return None;
+                        }
+
+                        let brightness = pixel_brightness(image, x as u32, y as u32);
+                        if brightness <= 0.0 {
+                            None
+                        } else {
+                            Some(settings.min_height + ((settings.max_height - settings.min_height) * brightness))
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        HeightmapTrack { rows }
+    }
+}
+
+impl TrackGenerator for HeightmapTrack {
+    fn next_row(&mut self, row: i32, _: &mut rng::RunRng) -> FloorRow {
+        if self.rows.is_empty() {
+            return FloorRow::default();
+        }
+
+        let heights = &self.rows[row as usize % self.rows.len()];
+        let columns = NUMBER_OF_COLUMNS / 2;
+        FloorRow::from_heights(|z| heights[(z + columns) as usize])
+    }
+}
+
+// between 0.0 and 1.0
+fn pixel_brightness(image: &Image, x: u32, y: u32) -> f32 {
+    let index = (y * image.texture_descriptor.size.width + x) as usize;
+    let data = &image.data;
+
+    match image.texture_descriptor.format {
+        // 16 bit grayscale pngs come through untouched
+        TextureFormat::R16Uint => {
+            let i = index * 2;
+            u16::from_ne_bytes([data[i], data[i + 1]]) as f32 / u16::MAX as f32
+        },
+        TextureFormat::Rgba16Uint => {
+            let i = index * 8;
+            u16::from_ne_bytes([data[i], data[i + 1]]) as f32 / u16::MAX as f32
+        },
+        // everything 8 bit gets expanded to rgba
+        _ => {
+            let i = index * 4;
+            (data[i] as f32 + data[i + 1] as f32 + data[i + 2] as f32) / (3.0 * 255.0)
+        },
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+use bevy::render::primitives::Aabb;
+use bevy_rapier3d::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use crate::{player::ability, rng};
+use super::{
+    heightfield_collider, row_mesh, track::TrackGenerator, ColliderMode, FloorChunk, FloorCollider,
+    FloorManager, FloorRow, FLOOR_CUBE_SIZE,
+};
+
+// how long a crumbling block hangs on after it's been touched
+pub static CRUMBLE_TIME: f32 = 0.4;
+// a pounded block never gets shorter than this, in half-cubes like Floor::height
+pub static MIN_POUNDED_HEIGHT: f32 = 1.0;
+// kinds are kept off the start of the track so nobody spawns onto a hazard
+static KIND_FREE_ROWS: i32 = 40;
+
+#[derive(Reflect, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum FloorKind {
+    #[default]
+    Normal,
+    // falls away shortly after it's touched
+    Crumbling,
+    // launches the player up
+    Bouncy,
+    // slows the player down
+    Sticky,
+    // speeds the player up
+    Boost,
+    // game over
+    Hazard,
+}
+
+pub static DEFAULT_KIND_WEIGHTS: [(FloorKind, u32); 5] = [
+    (FloorKind::Crumbling, 3),
+    (FloorKind::Bouncy, 2),
+    (FloorKind::Sticky, 2),
+    (FloorKind::Boost, 2),
+    (FloorKind::Hazard, 1),
+];
+
+#[derive(Clone)]
+pub struct KindSettings {
+    // chance of any one block being something other than normal. none by
+    // default, "--floor-kinds <density>" turns them on
+    pub density: f32,
+    pub weights: Vec<(FloorKind, u32)>,
+}
+
+impl Default for KindSettings {
+    fn default() -> Self {
+        KindSettings {
+            density: 0.0,
+            weights: DEFAULT_KIND_WEIGHTS.to_vec(),
+        }
+    }
+}
+
+// sprinkles special blocks over whatever another generator lays down
+pub struct KindScatter {
+    track_generator: Box<dyn TrackGenerator>,
+    settings: KindSettings,
+}
+
+impl KindScatter {
+    pub fn new(track_generator: Box<dyn TrackGenerator>, settings: KindSettings) -> Self {
+        KindScatter { track_generator, settings }
+    }
+}
+
+impl TrackGenerator for KindScatter {
+    fn next_row(&mut self, row: i32, track_rng: &mut rng::RunRng) -> FloorRow {
+        let mut floor_row = self.track_generator.next_row(row, track_rng);
+        if row < KIND_FREE_ROWS {
+            return floor_row;
+        }
+
+        if let Ok(index) = WeightedIndex::new(self.settings.weights.iter().map(|(_, weight)| *weight)) {
+            for block in floor_row.blocks.iter_mut() {
+                if track_rng.gen::<f32>() < self.settings.density {
+                    block.kind = self.settings.weights[index.sample(track_rng)].0;
+                }
+            }
+        }
+
+        floor_row
+    }
+
+    fn is_endless(&self) -> bool {
+        self.track_generator.is_endless()
+    }
+
+    fn end_row(&self) -> Option<i32> {
+        self.track_generator.end_row()
+    }
+}
+
+// the (row, column) and kind of every block the character controller
+// ran into this frame
+pub fn touched_blocks(
+    controller_output: &KinematicCharacterControllerOutput,
+    floor_manager: &FloorManager,
+    colliders: &Query<&FloorCollider>,
+    chunks: &Query<&FloorChunk>,
+) -> Vec<((i32, i32), FloorKind)> {
+    controller_output.collisions
+        .iter()
+        .filter_map(|collision| {
+            if let Ok(collider) = colliders.get(collision.entity) {
+                Some((collider.row, collider.z as i32))
+            } else if let Ok(chunk) = chunks.get(collision.entity) {
+                // a heightfield covers the whole row so go by where the player is
+                Some((chunk.row, (collision.character_translation.z / FLOOR_CUBE_SIZE).round() as i32))
+            } else {
+                None
+            }
+        })
+        .filter_map(|cell| {
+            let (chunk_entity, index) = *floor_manager.floor_grid.get(&cell)?;
+            let floor = chunks.get(chunk_entity).ok()?.blocks.get(index)?;
+            Some((cell, floor.kind))
+        })
+        .collect()
+}
+
+// takes crumbling blocks out of their rows once their time is up
+pub fn crumble_floors(
+    mut commands: Commands,
+    mut chunks: Query<(&mut FloorChunk, &Handle<Mesh>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut floor_manager: ResMut<FloorManager>,
+    time: Res<Time>,
+) {
+    let mut crumbled = vec!();
+    floor_manager.crumbling.retain(|cell, timer| {
+        *timer -= time.delta_seconds();
+        if *timer <= 0.0 {
+            crumbled.push(*cell);
+            false
+        } else {
+            true
+        }
+    });
+
+    for (row, column) in crumbled {
+        let (chunk_entity, index) = match floor_manager.floor_grid.remove(&(row, column)) {
+            Some(cell) => cell,
+            None => continue,
+        };
+        floor_manager.floor_heights.remove(&(row, column));
+        if let Some(collider) = floor_manager.floor_colliders.remove(&(row, column)) {
+            commands.entity(collider).despawn_recursive();
+        }
+
+        if let Ok((mut chunk, mesh)) = chunks.get_mut(chunk_entity) {
+            chunk.blocks.remove(index);
+            // everything after it in the row moved down one
+            for (index, floor) in chunk.blocks.iter().enumerate() {
+                floor_manager.floor_grid.insert((row, floor.z as i32), (chunk_entity, index));
+            }
+
+            chunk.lowest = chunk.blocks.iter().map(|floor| floor.height).fold(f32::MAX, f32::min);
+            if let Some(mesh) = meshes.get_mut(mesh) {
+                *mesh = row_mesh::build_row_mesh(&chunk.blocks);
+            }
+            if floor_manager.collider_mode == ColliderMode::Heightfield {
+                commands.entity(chunk_entity).insert(heightfield_collider(&chunk.blocks));
+            }
+            commands.entity(chunk_entity).remove::<Aabb>();
+        }
+    }
+}
+
+// pushes blocks a ground pound landed on down into the track
+pub fn press_floors(
+    mut commands: Commands,
+    mut chunks: Query<(&mut FloorChunk, &Handle<Mesh>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut floor_manager: ResMut<FloorManager>,
+) {
+    let pounded = std::mem::take(&mut floor_manager.pounded);
+    let mut pressed = vec!();
+    for (row, column) in pounded {
+        let (chunk_entity, index) = match floor_manager.floor_grid.get(&(row, column)) {
+            Some(cell) => *cell,
+            None => continue,
+        };
+
+        if let Ok((mut chunk, _)) = chunks.get_mut(chunk_entity) {
+            let floor = &mut chunk.blocks[index];
+            floor.height = (floor.height - ability::POUND_DEPTH).max(MIN_POUNDED_HEIGHT);
+            let height = floor.height;
+            floor_manager.floor_heights.insert((row, column), height);
+            if let Some(collider) = floor_manager.floor_colliders.get(&(row, column)) {
+                let half_size = FLOOR_CUBE_SIZE / 2.0;
+                commands.entity(*collider).insert(Collider::cuboid(half_size, half_size * height, half_size));
+            }
+            if !pressed.contains(&chunk_entity) {
+                pressed.push(chunk_entity);
+            }
+        }
+    }
+
+    for chunk_entity in pressed {
+        if let Ok((mut chunk, mesh)) = chunks.get_mut(chunk_entity) {
+            chunk.lowest = chunk.blocks.iter().map(|floor| floor.height).fold(f32::MAX, f32::min);
+            if let Some(mesh) = meshes.get_mut(mesh) {
+                *mesh = row_mesh::build_row_mesh(&chunk.blocks);
+            }
+            if floor_manager.collider_mode == ColliderMode::Heightfield {
+                commands.entity(chunk_entity).insert(heightfield_collider(&chunk.blocks));
+            }
+            commands.entity(chunk_entity).remove::<Aabb>();
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use crate::{player, rng, FLOOR_COLOR};
+use super::{track::TrackGenerator, FloorManager, FloorRow, NUMBER_OF_COLUMNS};
+
+#[derive(Clone)]
+pub struct LevelSettings {
+    pub path: String,
+}
+
+impl Default for LevelSettings {
+    fn default() -> Self {
+        LevelSettings {
+            path: "levels/first_steps.level.ron".to_string(),
+        }
+    }
+}
+
+pub struct LevelCompleteEvent;
+
+// a handcrafted course. heights are in the same units as Floor::height,
+// columns count from the left edge of the track
+#[derive(Deserialize, TypeUuid, Clone)]
+#[uuid = "5b0e64e1-7d0b-4c4f-9d7b-8f4c1f3c2a61"]
+pub struct Level {
+    pub name: String,
+    #[serde(default = "default_height")]
+    pub height: f32,
+    #[serde(default = "default_color")]
+    pub color: String,
+    pub rows: Vec<LevelRow>,
+    #[serde(default)]
+    pub donuts: Vec<LevelDonut>,
+    // the row the player has to reach, defaults to the last row
+    #[serde(default)]
+    pub end_row: Option<usize>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct LevelRow {
+    // how many times to lay this row down in a row
+    #[serde(default = "default_repeat")]
+    pub repeat: usize,
+    // one height for the whole row
+    #[serde(default)]
+    pub height: Option<f32>,
+    // or one per column, 0.0 leaves a hole
+    #[serde(default)]
+    pub heights: Vec<f32>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub colors: Vec<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LevelDonut {
+    pub row: usize,
+    pub column: i32,
+    // floats above a block of this height
+    pub height: f32,
+}
+
+fn default_height() -> f32 {
+    2.0
+}
+
+fn default_color() -> String {
+    FLOOR_COLOR.to_string()
+}
+
+fn default_repeat() -> usize {
+    1
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let level = ron::de::from_bytes::<Level>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+// lays the level's rows down once. past the end there's nothing
+pub struct LevelTrack {
+    rows: Vec<FloorRow>,
+    end_row: i32,
+}
+
+impl LevelTrack {
+    pub fn new(level: &Level) -> Self {
+        let columns = NUMBER_OF_COLUMNS / 2;
+        let level_color = Color::hex(&level.color).unwrap_or_else(|_| Color::hex(FLOOR_COLOR).unwrap());
+        let mut rows = vec!();
+
+        for level_row in &level.rows {
+            let row_color = level_row.color
+                .as_ref()
+                .and_then(|color| Color::hex(color).ok())
+                .unwrap_or(level_color);
+
+            for _ in 0..level_row.repeat {
+                let mut floor_row = FloorRow::from_heights(|z| {
+                    let column = (z + columns) as usize;
+                    let height = if level_row.heights.is_empty() {
+                        level_row.height.unwrap_or(level.height)
+                    } else {
+                        level_row.heights.get(column).copied().unwrap_or(0.0)
+                    };
+
+                    if height > 0.0 {
+                        Some(height)
+                    } else {
+                        None
+                    }
+                });
+
+                for block in floor_row.blocks.iter_mut() {
+                    let column = (block.z as i32 + columns) as usize;
+                    block.color = level_row.colors
+                        .get(column)
+                        .and_then(|color| Color::hex(color).ok())
+                        .unwrap_or(row_color);
+                }
+
+                rows.push(floor_row);
+            }
+        }
+
+        for donut in &level.donuts {
+            if let Some(floor_row) = rows.get_mut(donut.row) {
+                floor_row.donuts.push(((donut.column - columns) as f32, donut.height));
+            }
+        }
+
+        let end_row = level.end_row.unwrap_or(rows.len().max(1) - 1) as i32;
+        LevelTrack { rows, end_row }
+    }
+}
+
+impl TrackGenerator for LevelTrack {
+    fn next_row(&mut self, row: i32, _: &mut rng::RunRng) -> FloorRow {
+        match self.rows.get_mut(row as usize) {
+            Some(floor_row) => std::mem::take(floor_row),
+            None => FloorRow::default(),
+        }
+    }
+
+    fn is_endless(&self) -> bool {
+        false
+    }
+
+    fn end_row(&self) -> Option<i32> {
+        Some(self.end_row)
+    }
+}
+
+pub fn check_finish_line(
+    players: Query<&Transform, With<player::Player>>,
+    floor_manager: Res<FloorManager>,
+    mut level_complete_event_writer: EventWriter<LevelCompleteEvent>,
+) {
+    if let Some(end_x) = floor_manager.end_x() {
+        for transform in &players {
+            if transform.translation.x >= end_x {
+                level_complete_event_writer.send(LevelCompleteEvent);
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use rand::RngCore;
+use crate::rng;
+use super::{track::TrackGenerator, FloorRow};
+
+static MIN_HEIGHT: f32 = 1.0;
+
+#[derive(Clone, Copy)]
+pub struct NoiseSettings {
+    pub octaves: u32,
+    // in cycles per block
+    pub frequency: f32,
+    pub amplitude: f32,
+    pub base_height: f32,
+    pub persistence: f32,
+    pub lacunarity: f32,
+}
+
+impl Default for NoiseSettings {
+    fn default() -> Self {
+        NoiseSettings {
+            octaves: 3,
+            frequency: 0.04,
+            amplitude: 3.0,
+            base_height: 4.0,
+            persistence: 0.5,
+            lacunarity: 2.0,
+        }
+    }
+}
+
+// rolling hills sampled at (row, column). rows keep counting up as the
+// track is recycled so the hills never repeat
+pub struct NoiseTrack {
+    settings: NoiseSettings,
+    noise: Option<Perlin>,
+}
+
+impl NoiseTrack {
+    pub fn new(settings: NoiseSettings) -> Self {
+        NoiseTrack {
+            settings,
+            noise: None,
+        }
+    }
+}
+
+impl TrackGenerator for NoiseTrack {
+    fn next_row(&mut self, row: i32, track_rng: &mut rng::RunRng) -> FloorRow {
+        let settings = self.settings;
+        let noise = self.noise.get_or_insert_with(|| Perlin::new(track_rng.next_u64()));
+
+        FloorRow::from_heights(|z| {
+            let sample = noise.fractal(Vec2::new(row as f32, z as f32), &settings);
+            Some((settings.base_height + (sample * settings.amplitude)).max(MIN_HEIGHT))
+        })
+    }
+}
+
+pub struct Perlin {
+    seed: u64,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Perlin { seed }
+    }
+
+    // octaves of noise summed together, roughly between -1.0 and 1.0
+    pub fn fractal(&self, point: Vec2, settings: &NoiseSettings) -> f32 {
+        let mut total = 0.0;
+        let mut max_total = 0.0;
+        let mut frequency = settings.frequency;
+        let mut amplitude = 1.0;
+
+        for octave in 0..settings.octaves.max(1) {
+            // offset each octave so they don't all line up at the origin
+            let offset = Vec2::splat(octave as f32 * 17.31);
+            total += self.sample(point * frequency + offset) * amplitude;
+            max_total += amplitude;
+            amplitude *= settings.persistence;
+            frequency *= settings.lacunarity;
+        }
+
+        total / max_total
+    }
+
+    pub fn sample(&self, point: Vec2) -> f32 {
+        let cell = point.floor();
+        let local = point - cell;
+        let (x, y) = (cell.x as i32, cell.y as i32);
+
+        let corner = |dx: i32, dy: i32| {
+            self.gradient(x + dx, y + dy).dot(local - Vec2::new(dx as f32, dy as f32))
+        };
+
+        let fade_x = fade(local.x);
+        let fade_y = fade(local.y);
+        let bottom = lerp(corner(0, 0), corner(1, 0), fade_x);
+        let top = lerp(corner(0, 1), corner(1, 1), fade_x);
+
+        // plain 2d perlin tops out around 0.7
+        (lerp(bottom, top, fade_y) * std::f32::consts::SQRT_2).clamp(-1.0, 1.0)
+    }
+
+    fn gradient(&self, x: i32, y: i32) -> Vec2 {
+        let mut hash = self.seed ^ (((x as u32 as u64) << 32) | y as u32 as u64);
+        // splitmix64 finalizer
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+        hash ^= hash >> 31;
+
+        let angle = (hash % 1024) as f32 / 1024.0 * std::f32::consts::TAU;
+        Vec2::new(angle.cos(), angle.sin())
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + ((b - a) * t)
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+use crate::{food, player};
+use super::{platform::Platform, FloorChunk, FloorCollider, FloorManager, FLOOR_CUBE_SIZE};
+
+// how far the player gets from zero before everything is moved back
+static ORIGIN_SHIFT_DISTANCE: f32 = 300.0;
+
+// the track only goes along +x so on a long enough run the translations get
+// big enough for f32 to start jittering. every so often everything on the
+// track gets moved back by a whole number of rows and FloorManager remembers
+// how far, so FloorManager::track_x still gives the real distance
+pub fn shift_origin(
+    mut transforms: Query<
+        (&mut Transform, Option<&player::Player>),
+        Or<(With<FloorChunk>, With<FloorCollider>, With<Platform>, With<food::Food>, With<player::Player>, With<Camera3d>)>,
+    >,
+    mut floor_manager: ResMut<FloorManager>,
+) {
+    let player_x = transforms
+        .iter()
+        .filter(|(_, player)| player.is_some())
+        .map(|(transform, _)| transform.translation.x)
+        .fold(f32::MIN, f32::max);
+    if player_x < ORIGIN_SHIFT_DISTANCE {
+        return;
+    }
+
+    let rows = (player_x / FLOOR_CUBE_SIZE).floor() as i32;
+    let shift = rows as f32 * FLOOR_CUBE_SIZE;
+    for (mut transform, _) in &mut transforms {
+        transform.translation.x -= shift;
+    }
+
+    floor_manager.origin_row += rows;
+}
@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use super::kind::FloorKind;
+
+// how many shades each color channel gets snapped to
+static PALETTE_LEVELS: f32 = 32.0;
+
+pub static CRUMBLING_COLOR: &str = "b08968";
+pub static BOUNCY_COLOR: &str = "7bdff2";
+pub static STICKY_COLOR: &str = "8fb339";
+pub static BOOST_COLOR: &str = "ffd23f";
+pub static HAZARD_COLOR: &str = "e63946";
+
+// block colors get snapped to a fixed set of shades so the same color
+// always hands back the same material instead of a new one every row
+#[derive(Default, Resource)]
+pub struct Palette {
+    materials: HashMap<[u8; 4], Handle<StandardMaterial>>,
+}
+
+impl Palette {
+    pub fn quantize(color: Color) -> Color {
+        let [r, g, b, a] = Palette::key(color);
+        Color::rgba(
+            r as f32 / PALETTE_LEVELS,
+            g as f32 / PALETTE_LEVELS,
+            b as f32 / PALETTE_LEVELS,
+            a as f32 / PALETTE_LEVELS,
+        )
+    }
+
+    pub fn material(
+        &mut self,
+        color: Color,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Handle<StandardMaterial> {
+        self.materials
+            .entry(Palette::key(color))
+            .or_insert_with(|| materials.add(StandardMaterial {
+                base_color: Palette::quantize(color),
+                unlit: false,
+                ..default()
+            }))
+            .clone()
+    }
+
+    // special blocks always come out the same color, normal ones keep theirs
+    pub fn kind_color(kind: FloorKind) -> Option<Color> {
+        let hex = match kind {
+            FloorKind::Normal => return None,
+            FloorKind::Crumbling => CRUMBLING_COLOR,
+            FloorKind::Bouncy => BOUNCY_COLOR,
+            FloorKind::Sticky => STICKY_COLOR,
+            FloorKind::Boost => BOOST_COLOR,
+            FloorKind::Hazard => HAZARD_COLOR,
+        };
+        Color::hex(hex).ok().map(Palette::quantize)
+    }
+
+    fn key(color: Color) -> [u8; 4] {
+        let [r, g, b, a] = color.as_rgba_f32();
+        [r, g, b, a].map(|channel| (channel.clamp(0.0, 1.0) * PALETTE_LEVELS).round() as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rng, FLOOR_COLOR};
+
+    #[test]
+    fn materials_stay_bounded_over_a_long_run() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<StandardMaterial>();
+        let mut materials = app.world.resource_mut::<Assets<StandardMaterial>>();
+        let mut palette = Palette::default();
+        let mut run_rng = rng::RunRng::from_seed(1234);
+        let kinds = [
+            FloorKind::Normal,
+            FloorKind::Crumbling,
+            FloorKind::Bouncy,
+            FloorKind::Sticky,
+            FloorKind::Boost,
+            FloorKind::Hazard,
+        ];
+
+        // colored the way spawn_floors does it, a few thousand rows' worth
+        let mut after_first_lap = 0;
+        for row in 0..5000 {
+            for column in 0..30 {
+                let color = Palette::kind_color(kinds[column % kinds.len()]).unwrap_or_else(|| {
+                    let mut color = Color::hex(FLOOR_COLOR).unwrap();
+                    let color_x = run_rng.random_number();
+                    color.set_g(color.g() - (color_x * 0.1));
+                    color.set_r(color.r() - (color_x * 0.1));
+                    color.set_b(color.b() - (color_x * 0.1));
+                    Palette::quantize(color)
+                });
+                palette.material(color, &mut materials);
+            }
+
+            if row == 200 {
+                after_first_lap = materials.len();
+            }
+        }
+
+        assert_eq!(materials.len(), after_first_lap);
+        assert_eq!(materials.len(), palette.materials.len());
+        assert!(materials.len() < 20, "{} materials", materials.len());
+    }
+
+    #[test]
+    fn quantized_colors_keep_their_shade() {
+        let color = Palette::quantize(Color::hex(FLOOR_COLOR).unwrap());
+        assert_eq!(Palette::key(Palette::quantize(color)), Palette::key(color));
+    }
+}
@@ -0,0 +1,209 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+use std::f32::consts::TAU;
+use crate::{rng, CleanupMarker};
+use super::{row_mesh, track::TrackGenerator, Floor, FloorManager, FloorRow, FLOOR_CUBE_SIZE};
+
+// platforms are kept off the start of the track like special blocks are
+static PLATFORM_FREE_ROWS: i32 = 40;
+
+// how a group of blocks moves, worked out from the time so every row
+// of the same platform stays lined up
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlatformMotion {
+    // up and down around where it was placed
+    Bob { amplitude: f32, period: f32, phase: f32 },
+    // back and forth between where it was placed and offset from there
+    Slide { offset: Vec3, period: f32, phase: f32 },
+}
+
+impl PlatformMotion {
+    pub fn offset(&self, seconds: f32) -> Vec3 {
+        match *self {
+            PlatformMotion::Bob { amplitude, period, phase } => {
+                Vec3::Y * amplitude * ((seconds / period) * TAU + phase).sin()
+            },
+            PlatformMotion::Slide { offset, period, phase } => {
+                offset * (0.5 - (0.5 * ((seconds / period) * TAU + phase).cos()))
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PlatformSettings {
+    // chance of a platform starting on any row that isn't already part of one.
+    // none by default, "--platforms <density>" turns them on
+    pub density: f32,
+}
+
+impl Default for PlatformSettings {
+    fn default() -> Self {
+        PlatformSettings {
+            density: 0.0,
+        }
+    }
+}
+
+// the moving blocks from one row, drawn and collided with as one piece
+#[derive(Component)]
+pub struct Platform {
+    pub row: i32,
+    pub motion: PlatformMotion,
+    // how far it moved since last frame, anything standing on it gets moved the same
+    pub delta: Vec3,
+    offset: Vec3,
+}
+
+// turns a few columns over a few rows of another generator's track into a platform
+pub struct PlatformScatter {
+    track_generator: Box<dyn TrackGenerator>,
+    settings: PlatformSettings,
+    rows_left: i32,
+    columns: (i32, i32),
+    motion: Option<PlatformMotion>,
+}
+
+impl PlatformScatter {
+    pub fn new(track_generator: Box<dyn TrackGenerator>, settings: PlatformSettings) -> Self {
+        PlatformScatter {
+            track_generator,
+            settings,
+            rows_left: 0,
+            columns: (0, 0),
+            motion: None,
+        }
+    }
+
+    // placed inside the row it starts on since the track can be any width
+    fn start_platform(&mut self, track_rng: &mut rng::RunRng, floor_row: &FloorRow) {
+        let first = floor_row.offset - (floor_row.width / 2);
+        let end = first + floor_row.width;
+        let width = track_rng.gen_range(3..6);
+        let phase = track_rng.random_in_f32_range(0.0, TAU);
+        self.rows_left = track_rng.gen_range(3..7);
+
+        if track_rng.gen_bool(0.5) {
+            self.columns = {
+                let start = track_rng.gen_range(first..(end - width).max(first + 1));
+                (start, start + width)
+            };
+            self.motion = Some(PlatformMotion::Bob {
+                amplitude: track_rng.random_in_f32_range(0.3, 1.0),
+                period: track_rng.random_in_f32_range(2.0, 4.0),
+                phase,
+            });
+        } else {
+            // slides across a gap cut out of the track for it
+            let travel = track_rng.gen_range(4..10).min((floor_row.width - width - 1).max(1));
+            let start = track_rng.gen_range(first..(end - width - travel).max(first + 1));
+            self.columns = (start, start + width);
+            self.motion = Some(PlatformMotion::Slide {
+                offset: Vec3::Z * travel as f32 * FLOOR_CUBE_SIZE,
+                period: track_rng.random_in_f32_range(3.0, 5.0),
+                phase,
+            });
+        }
+    }
+}
+
+impl TrackGenerator for PlatformScatter {
+    fn next_row(&mut self, row: i32, track_rng: &mut rng::RunRng) -> FloorRow {
+        let mut floor_row = self.track_generator.next_row(row, track_rng);
+        if row < PLATFORM_FREE_ROWS {
+            return floor_row;
+        }
+
+        if self.rows_left <= 0 && track_rng.gen::<f32>() < self.settings.density {
+            self.start_platform(track_rng, &floor_row);
+        }
+
+        if self.rows_left > 0 {
+            self.rows_left -= 1;
+            let (start, end) = self.columns;
+            let motion = self.motion;
+            let gap_end = match motion {
+                Some(PlatformMotion::Slide { offset, .. }) => end + (offset.z / FLOOR_CUBE_SIZE).round() as i32,
+                _ => end,
+            };
+
+            floor_row.blocks.retain(|block| {
+                let z = block.z as i32;
+                (start..end).contains(&z) || !(start..gap_end).contains(&z)
+            });
+            for block in floor_row.blocks.iter_mut() {
+                if (start..end).contains(&(block.z as i32)) {
+                    block.motion = motion;
+                    block.kind = super::kind::FloorKind::Normal;
+                }
+            }
+        }
+
+        floor_row
+    }
+
+    fn is_endless(&self) -> bool {
+        self.track_generator.is_endless()
+    }
+
+    fn end_row(&self) -> Option<i32> {
+        self.track_generator.end_row()
+    }
+}
+
+pub fn spawn_platform(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    floor_manager: &FloorManager,
+    row: i32,
+    blocks: &[Floor],
+) {
+    let motion = match blocks.iter().find_map(|block| block.motion) {
+        Some(motion) => motion,
+        None => return,
+    };
+
+    let half_size = FLOOR_CUBE_SIZE / 2.0;
+    let collider = Collider::compound(
+        blocks
+            .iter()
+            .map(|block| (
+                Vec3::new(0.0, 0.0, block.z * FLOOR_CUBE_SIZE),
+                Quat::IDENTITY,
+                Collider::cuboid(half_size, half_size * block.height, half_size),
+            ))
+            .collect()
+    );
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(row_mesh::build_row_mesh(blocks)),
+            material: floor_manager.floor_material.clone(),
+            transform: Transform::from_xyz(floor_manager.row_x(row), 0.0, 0.0),
+            ..default()
+        },
+        RigidBody::KinematicPositionBased,
+        collider,
+        Platform {
+            row,
+            motion,
+            delta: Vec3::ZERO,
+            offset: Vec3::ZERO,
+        },
+        CleanupMarker,
+    ));
+}
+
+pub fn move_platforms(
+    mut platforms: Query<(&mut Platform, &mut Transform)>,
+    floor_manager: Res<FloorManager>,
+    time: Res<Time>,
+) {
+    for (mut platform, mut transform) in &mut platforms {
+        let offset = platform.motion.offset(time.elapsed_seconds());
+        platform.delta = offset - platform.offset;
+        platform.offset = offset;
+        transform.translation = Vec3::new(floor_manager.row_x(platform.row), 0.0, 0.0) + offset;
+    }
+}
@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use super::{Floor, FLOOR_CUBE_SIZE};
+
+// normal, then two edges of the face whose cross product is the normal
+// so the corners below wind counter-clockwise from the outside
+static FACES: [(Vec3, Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::Y, Vec3::Z),
+    (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+    (Vec3::Y, Vec3::Z, Vec3::X),
+    (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+    (Vec3::Z, Vec3::X, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::Y, Vec3::X),
+];
+
+static CORNERS: [(f32, f32); 4] = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+
+// every block in a row as one mesh, relative to the row's position.
+// each block is colored through its vertex colors so the whole row
+// can share one material
+pub fn build_row_mesh(blocks: &[Floor]) -> Mesh {
+    let vertex_count = blocks.len() * FACES.len() * CORNERS.len();
+    let mut positions = Vec::with_capacity(vertex_count);
+    let mut normals = Vec::with_capacity(vertex_count);
+    let mut uvs = Vec::with_capacity(vertex_count);
+    let mut colors = Vec::with_capacity(vertex_count);
+    let mut indices = Vec::with_capacity(blocks.len() * FACES.len() * 6);
+
+    for block in blocks {
+        let half_size = Vec3::new(FLOOR_CUBE_SIZE, FLOOR_CUBE_SIZE * block.height, FLOOR_CUBE_SIZE) / 2.0;
+        let center = Vec3::new(0.0, 0.0, block.z * FLOOR_CUBE_SIZE);
+        let color = block.color.as_linear_rgba_f32();
+
+        for (normal, edge_u, edge_v) in FACES {
+            let first = positions.len() as u32;
+            for (u, v) in CORNERS {
+                let corner = normal + (edge_u * u) + (edge_v * v);
+                positions.push((center + (corner * half_size)).to_array());
+                normals.push(normal.to_array());
+                uvs.push([(u + 1.0) / 2.0, (v + 1.0) / 2.0]);
+                colors.push(color);
+            }
+
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
@@ -0,0 +1,145 @@
+use rand::Rng;
+use std::collections::HashMap;
+use crate::rng;
+use super::{track::TrackGenerator, Floor, FloorRow, NUMBER_OF_COLUMNS};
+
+static MIN_WIDTH: i32 = 10;
+static MAX_WIDTH: i32 = 50;
+// how far either way the middle of the track can wander, in columns
+static MAX_DRIFT: i32 = 20;
+// the start of the track stays the way it was generated
+static SHAPE_FREE_ROWS: i32 = 40;
+
+#[derive(Clone)]
+pub struct ShapeSettings {
+    // chance of the track starting to change shape on any row it isn't already.
+    // it keeps its shape by default, "--track-shapes <density>" turns this on
+    pub density: f32,
+}
+
+impl Default for ShapeSettings {
+    fn default() -> Self {
+        ShapeSettings {
+            density: 0.0,
+        }
+    }
+}
+
+// narrows, widens and drifts another generator's track from side to side.
+// the rows it hands back are resampled from the generated ones so any
+// generator can go through it
+pub struct TrackShape {
+    track_generator: Box<dyn TrackGenerator>,
+    settings: ShapeSettings,
+    width: f32,
+    offset: f32,
+    start: (f32, f32),
+    target: (f32, f32),
+    length: i32,
+    rows_left: i32,
+}
+
+impl TrackShape {
+    pub fn new(track_generator: Box<dyn TrackGenerator>, settings: ShapeSettings) -> Self {
+        let start = (NUMBER_OF_COLUMNS as f32, 0.0);
+        TrackShape {
+            track_generator,
+            settings,
+            width: start.0,
+            offset: start.1,
+            start,
+            target: start,
+            length: 0,
+            rows_left: 0,
+        }
+    }
+
+    fn pick_shape(&mut self, track_rng: &mut rng::RunRng) {
+        self.start = (self.width, self.offset);
+        self.target = match track_rng.gen_range(0..4) {
+            // narrow
+            0 => (track_rng.gen_range(MIN_WIDTH..NUMBER_OF_COLUMNS) as f32, self.offset),
+            // widen
+            1 => (track_rng.gen_range(NUMBER_OF_COLUMNS..=MAX_WIDTH) as f32, self.offset),
+            // drift
+            2 => {
+                let drift = track_rng.gen_range(5..15) as f32 * track_rng.random_number().signum();
+                (self.width, (self.offset + drift).clamp(-MAX_DRIFT as f32, MAX_DRIFT as f32))
+            },
+            // back to how it started
+            _ => (NUMBER_OF_COLUMNS as f32, 0.0),
+        };
+        self.length = track_rng.gen_range(20..60);
+        self.rows_left = self.length;
+    }
+}
+
+impl TrackGenerator for TrackShape {
+    fn next_row(&mut self, row: i32, track_rng: &mut rng::RunRng) -> FloorRow {
+        let floor_row = self.track_generator.next_row(row, track_rng);
+        if row < SHAPE_FREE_ROWS {
+            return floor_row;
+        }
+
+        if self.rows_left <= 0 && track_rng.gen::<f32>() < self.settings.density {
+            self.pick_shape(track_rng);
+        }
+
+        if self.rows_left > 0 {
+            self.rows_left -= 1;
+            // eases in and out so the edges curve instead of kinking
+            let progress = 1.0 - (self.rows_left as f32 / self.length as f32);
+            let t = progress * progress * (3.0 - (2.0 * progress));
+            self.width = self.start.0 + ((self.target.0 - self.start.0) * t);
+            self.offset = self.start.1 + ((self.target.1 - self.start.1) * t);
+        }
+
+        reshape(floor_row, self.width.round() as i32, self.offset.round() as i32)
+    }
+
+    fn is_endless(&self) -> bool {
+        self.track_generator.is_endless()
+    }
+
+    fn end_row(&self) -> Option<i32> {
+        self.track_generator.end_row()
+    }
+}
+
+// lays a generated row out across width columns centered on offset. narrower
+// rows take the middle of it, wider ones carry its edges out further
+fn reshape(floor_row: FloorRow, width: i32, offset: i32) -> FloorRow {
+    if width == floor_row.width && offset == floor_row.offset {
+        return floor_row;
+    }
+
+    let columns = floor_row.width / 2;
+    let generated = floor_row.blocks
+        .iter()
+        .map(|block| (block.z as i32 - floor_row.offset, *block))
+        .collect::<HashMap<i32, Floor>>();
+
+    let mut reshaped = FloorRow {
+        width,
+        offset,
+        ..Default::default()
+    };
+    let first = offset - (width / 2);
+    for z in first..(first + width) {
+        let column = (z - offset).clamp(-columns, columns - 1);
+        if let Some(block) = generated.get(&column) {
+            reshaped.blocks.push_front(Floor {
+                z: z as f32,
+                ..*block
+            });
+        }
+    }
+
+    let last = first + width - 1;
+    reshaped.donuts = floor_row.donuts
+        .into_iter()
+        .map(|(z, height)| ((z + (offset - floor_row.offset) as f32).clamp(first as f32, last as f32), height))
+        .collect();
+
+    reshaped
+}
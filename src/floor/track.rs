@@ -0,0 +1,298 @@
+use bevy::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use crate::{assets, rng, RunArgs};
+use super::{heightmap, kind, level, noise, platform, shape, ColliderMode, FloorRow, NUMBER_OF_COLUMNS};
+
+static STARTING_LEVEL: f32 = 2.0;
+static MIN_LEVEL: f32 = 1.0;
+static MAX_LEVEL: f32 = 8.0;
+static RUNWAY_ROWS: i32 = 40;
+
+// FloorManager asks this for every new row along the track, in order
+pub trait TrackGenerator: Send + Sync {
+    fn next_row(&mut self, row: i32, track_rng: &mut rng::RunRng) -> FloorRow;
+
+    // endless tracks keep growing and get random donuts every lap
+    fn is_endless(&self) -> bool {
+        true
+    }
+
+    // the row that counts as the finish line
+    fn end_row(&self) -> Option<i32> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TrackMode {
+    Flat,
+    Segments,
+    Noise,
+    Heightmap,
+    Level,
+}
+
+#[derive(Resource)]
+pub struct TrackSettings {
+    pub mode: TrackMode,
+    pub segment_weights: Vec<(Segment, u32)>,
+    pub noise: noise::NoiseSettings,
+    pub heightmap: heightmap::HeightmapSettings,
+    pub level: level::LevelSettings,
+    pub collider_mode: ColliderMode,
+    pub kinds: kind::KindSettings,
+    pub platforms: platform::PlatformSettings,
+    pub shape: shape::ShapeSettings,
+}
+
+impl Default for TrackSettings {
+    fn default() -> Self {
+        TrackSettings {
+            mode: TrackMode::Flat,
+            segment_weights: DEFAULT_SEGMENT_WEIGHTS.to_vec(),
+            noise: noise::NoiseSettings::default(),
+            heightmap: heightmap::HeightmapSettings::default(),
+            level: level::LevelSettings::default(),
+            collider_mode: ColliderMode::default(),
+            kinds: kind::KindSettings::default(),
+            platforms: platform::PlatformSettings::default(),
+            shape: shape::ShapeSettings::default(),
+        }
+    }
+}
+
+impl TrackSettings {
+    // the track chosen on the command line, see RunArgs
+    pub fn from_run_args(run_args: &RunArgs) -> Self {
+        let mut track_settings = TrackSettings::default();
+        if let Some(path) = &run_args.heightmap {
+            track_settings.mode = TrackMode::Heightmap;
+            track_settings.heightmap.path = path.clone();
+        }
+        if let Some(path) = &run_args.level {
+            track_settings.mode = TrackMode::Level;
+            track_settings.level.path = path.clone();
+        }
+        if let Some(mode) = run_args.track {
+            track_settings.mode = mode;
+        }
+        if let Some(collider_mode) = run_args.colliders {
+            track_settings.collider_mode = collider_mode;
+        }
+        if let Some(density) = run_args.floor_kinds {
+            track_settings.kinds.density = density;
+        }
+        if let Some(density) = run_args.platforms {
+            track_settings.platforms.density = density;
+        }
+        if let Some(density) = run_args.track_shapes {
+            track_settings.shape.density = density;
+        }
+
+        track_settings
+    }
+
+    pub fn generator(
+        &self,
+        game_assets: &assets::GameAssets,
+        images: &Assets<Image>,
+        levels: &Assets<level::Level>,
+    ) -> Box<dyn TrackGenerator> {
+        let track_generator: Box<dyn TrackGenerator> = match self.mode {
+            TrackMode::Flat => Box::new(FlatTrack),
+            TrackMode::Segments => Box::new(SegmentTrack::new(self.segment_weights.clone())),
+            TrackMode::Noise => Box::new(noise::NoiseTrack::new(self.noise)),
+            TrackMode::Heightmap => {
+                if let Some(image) = images.get(&game_assets.track_heightmap) {
+                    Box::new(heightmap::HeightmapTrack::from_image(image, &self.heightmap))
+                } else {
+                    warn!("heightmap {} isn't loaded, falling back to a flat track", self.heightmap.path);
+                    Box::new(FlatTrack)
+                }
+            },
+            TrackMode::Level => {
+                if let Some(level) = levels.get(&game_assets.level) {
+                    info!("playing {}", level.name);
+                    Box::new(level::LevelTrack::new(level))
+                } else {
+                    warn!("level {} isn't loaded, falling back to a flat track", self.level.path);
+                    Box::new(FlatTrack)
+                }
+            },
+        };
+
+        // handcrafted levels are left the way they were made
+        if self.mode == TrackMode::Level {
+            return track_generator;
+        }
+
+        let track_generator: Box<dyn TrackGenerator> = if self.shape.density > 0.0 {
+            Box::new(shape::TrackShape::new(track_generator, self.shape.clone()))
+        } else {
+            track_generator
+        };
+
+        let track_generator: Box<dyn TrackGenerator> = if self.kinds.density > 0.0 {
+            Box::new(kind::KindScatter::new(track_generator, self.kinds.clone()))
+        } else {
+            track_generator
+        };
+
+        // platforms go over the top so they can clear out special blocks in their way
+        if self.platforms.density > 0.0 {
+            Box::new(platform::PlatformScatter::new(track_generator, self.platforms.clone()))
+        } else {
+            track_generator
+        }
+    }
+}
+
+// the original track, one long strip at the same height
+pub struct FlatTrack;
+
+impl TrackGenerator for FlatTrack {
+    fn next_row(&mut self, _: i32, _: &mut rng::RunRng) -> FloorRow {
+        FloorRow::from_heights(|_| Some(STARTING_LEVEL))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Segment {
+    Flat,
+    Gap,
+    Ramp,
+    Staircase,
+    Pillars,
+    NarrowBridge,
+}
+
+pub static DEFAULT_SEGMENT_WEIGHTS: [(Segment, u32); 6] = [
+    (Segment::Flat, 6),
+    (Segment::Gap, 2),
+    (Segment::Ramp, 3),
+    (Segment::Staircase, 2),
+    (Segment::Pillars, 2),
+    (Segment::NarrowBridge, 2),
+];
+
+// strings together segments picked from a weighted table
+pub struct SegmentTrack {
+    weights: Vec<(Segment, u32)>,
+    segment: Segment,
+    length: i32,
+    rows_left: i32,
+    level: f32,
+    start_level: f32,
+    target_level: f32,
+    step_rows: i32,
+    bridge_columns: (i32, i32),
+}
+
+impl SegmentTrack {
+    pub fn new(weights: Vec<(Segment, u32)>) -> Self {
+        SegmentTrack {
+            weights,
+            segment: Segment::Flat,
+            length: RUNWAY_ROWS,
+            rows_left: RUNWAY_ROWS,
+            level: STARTING_LEVEL,
+            start_level: STARTING_LEVEL,
+            target_level: STARTING_LEVEL,
+            step_rows: 1,
+            bridge_columns: (0, 0),
+        }
+    }
+
+    fn pick_segment(&mut self, track_rng: &mut rng::RunRng) {
+        self.segment = match WeightedIndex::new(self.weights.iter().map(|(_, weight)| *weight)) {
+            Ok(index) => self.weights[index.sample(track_rng)].0,
+            Err(_) => Segment::Flat,
+        };
+
+        self.start_level = self.level;
+        self.target_level = self.level;
+        self.length = match self.segment {
+            Segment::Flat => track_rng.gen_range(10..25),
+            Segment::Gap => track_rng.gen_range(4..10),
+            Segment::Ramp => {
+                let rise = track_rng.random_in_f32_range(1.0, 3.0) * track_rng.random_number().signum();
+                self.target_level = (self.level + rise).clamp(MIN_LEVEL, MAX_LEVEL);
+                track_rng.gen_range(10..20)
+            },
+            Segment::Staircase => {
+                let steps = track_rng.gen_range(3..6);
+                let direction = if self.level + steps as f32 > MAX_LEVEL { -1.0 } else { 1.0 };
+                self.target_level = (self.level + (steps as f32 * direction)).clamp(MIN_LEVEL, MAX_LEVEL);
+                self.step_rows = track_rng.gen_range(3..6);
+                steps * self.step_rows
+            },
+            Segment::Pillars => track_rng.gen_range(15..30),
+            Segment::NarrowBridge => {
+                let columns = NUMBER_OF_COLUMNS / 2;
+                let width = track_rng.gen_range(3..7);
+                let start = track_rng.gen_range(-columns..(columns - width));
+                self.bridge_columns = (start, start + width);
+                track_rng.gen_range(10..25)
+            },
+        };
+        self.rows_left = self.length;
+    }
+}
+
+impl TrackGenerator for SegmentTrack {
+    fn next_row(&mut self, _: i32, track_rng: &mut rng::RunRng) -> FloorRow {
+        if self.rows_left <= 0 {
+            self.pick_segment(track_rng);
+        }
+
+        let row_in_segment = self.length - self.rows_left;
+        self.rows_left -= 1;
+        let level = self.level;
+
+        match self.segment {
+            Segment::Flat => FloorRow::from_heights(|_| Some(level)),
+            Segment::Gap => FloorRow::from_heights(|_| None),
+            Segment::Ramp => {
+                let progress = (row_in_segment + 1) as f32 / self.length as f32;
+                self.level = self.start_level + ((self.target_level - self.start_level) * progress);
+                let level = self.level;
+                FloorRow::from_heights(|_| Some(level))
+            },
+            Segment::Staircase => {
+                let step = (row_in_segment / self.step_rows) + 1;
+                let direction = (self.target_level - self.start_level).signum();
+                self.level = (self.start_level + (step as f32 * direction)).clamp(MIN_LEVEL, MAX_LEVEL);
+                let level = self.level;
+                FloorRow::from_heights(|_| Some(level))
+            },
+            Segment::Pillars => {
+                // most rows are open, every so often a few columns shoot up
+                let mut pillars = vec!();
+                if row_in_segment % 4 == 0 {
+                    for _ in 0..track_rng.gen_range(1..4) {
+                        let columns = NUMBER_OF_COLUMNS / 2;
+                        pillars.push(track_rng.gen_range(-columns..columns));
+                    }
+                }
+                let pillar_height = level + track_rng.random_in_f32_range(8.0, 14.0);
+                FloorRow::from_heights(|z| {
+                    if pillars.contains(&z) {
+                        Some(pillar_height)
+                    } else {
+                        Some(level)
+                    }
+                })
+            },
+            Segment::NarrowBridge => {
+                let (start, end) = self.bridge_columns;
+                FloorRow::from_heights(|z| {
+                    if z >= start && z < end {
+                        Some(level)
+                    } else {
+                        None
+                    }
+                })
+            },
+        }
+    }
+}
@@ -1,81 +1,691 @@
+use bevy::gltf::Gltf;
 use bevy::prelude::*;
-use crate::{floor, CleanupMarker, FOOD_COLOR, random_in_f32_range, player, audio, assets};
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+use crate::{combo, floor, CleanupMarker, player, powerup, audio, assets, rng, AppState};
+use std::collections::HashMap;
 use std::f32::consts::TAU;
 
+pub mod collectible;
+pub mod pattern;
+
+// how close the middle of the player has to get to a donut to pick it up,
+// the donut's sensor reaches out this far less half the player
+pub static PICKUP_DISTANCE: f32 = 1.0;
+// how far over the floor a donut floats so it isn't sunk into it
+static DONUT_CLEARANCE: f32 = 0.5;
+// how many random spots get tried before giving up on a reachable one
+static PLACEMENT_TRIES: usize = 30;
+// how many places along a block, up to as late as coyote time allows,
+// a jump from it is tried from
+static TAKEOFF_SAMPLES: usize = 8;
+// on top of the usual per donut for picking up every donut in a pattern
+static PATTERN_BONUS: usize = 50;
+// how far away and how fast a magnet pulls donuts in
+static MAGNET_RADIUS: f32 = 5.0;
+static MAGNET_SPEED: f32 = 12.0;
+
 pub struct FoodPlugin;
 impl Plugin for FoodPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_event::<SpawnFoodEvent>();
+            .add_asset::<collectible::CollectibleSet>()
+            .init_asset_loader::<collectible::CollectibleSetLoader>()
+            .init_resource::<collectible::Collectibles>()
+            .init_resource::<FoodStats>()
+            .init_resource::<PatternGroups>()
+            .add_system(collectible::start_collectibles.in_schedule(OnEnter(AppState::InGame)))
+            .add_system(start_food_stats.in_schedule(OnEnter(AppState::InGame)))
+            .add_system(start_pattern_groups.in_schedule(OnEnter(AppState::InGame)))
+            .add_event::<SpawnFoodEvent>()
+            .add_event::<FoodCollectedEvent>();
     }
 }
 
 #[derive(Component, Reflect, Default, Clone, Copy)]
 pub struct Food {
+    // donuts spawned together as a pattern share a group
+    pub group: Option<u32>,
+    // which of the Collectibles types it is
+    pub kind: usize,
+}
+
+// a missed donut waiting in the pool to be spawned again. it keeps the shared
+// mesh, just not Food or its sensor, so nothing else sees it
+#[derive(Component)]
+pub struct PooledFood;
+
+#[derive(Resource, Default)]
+pub struct FoodStats {
+    // donuts the camera went past before they were picked up
+    pub missed: usize,
+}
+
+fn start_food_stats(mut food_stats: ResMut<FoodStats>) {
+    *food_stats = FoodStats::default();
+}
+
+// how many donuts each pattern still has out on the track. picking up the
+// last of them finishes the pattern, missing any of them fails it
+#[derive(Resource, Default)]
+pub struct PatternGroups {
+    next: u32,
+    // only patterns that can still be finished are in here
+    left: HashMap<u32, usize>,
 }
 
-pub struct SpawnFoodEvent;
+impl PatternGroups {
+    // a new group for a pattern of this many donuts
+    pub fn start(&mut self, donuts: usize) -> u32 {
+        self.next += 1;
+        self.left.insert(self.next, donuts);
+        self.next
+    }
+
+    // true if that was the last of the group's donuts
+    pub fn collect(&mut self, group: u32) -> bool {
+        let finished = match self.left.get_mut(&group) {
+            Some(left) => {
+                *left = left.saturating_sub(1);
+                *left == 0
+            },
+            None => false,
+        };
+        if finished {
+            self.left.remove(&group);
+        }
+
+        finished
+    }
 
+    pub fn miss(&mut self, group: u32) {
+        self.left.remove(&group);
+    }
+}
+
+fn start_pattern_groups(mut pattern_groups: ResMut<PatternGroups>) {
+    *pattern_groups = PatternGroups::default();
+}
 
+// without a position the donuts go somewhere random near the end of the track.
+// positions are along the track, spawn_food puts them wherever the floating
+// origin has got to
+#[derive(Default)]
+pub struct SpawnFoodEvent {
+    pub position: Option<Vec3>,
+    pub pattern: pattern::DonutPattern,
+}
+
+// sent by collect_food for each donut picked up, the donut itself is already
+// on its way out by the time anything reads it
+pub struct FoodCollectedEvent {
+    pub collector: Entity,
+    pub food: Entity,
+    // which of the Collectibles types it was
+    pub kind: usize,
+    pub group: Option<u32>,
+}
+
+// spins and bobs the donuts, and pulls them in while there's a magnet going
 pub fn update_food(
-    mut commands: Commands,
-    mut foods: Query<(Entity, &Food, &mut Transform), Without<player::Player>>,
-    mut floor_manager: ResMut<floor::FloorManager>,
-    mut player: Query<(&mut player::Player, &Transform)>,
+    mut foods: Query<&mut Transform, (With<Food>, Without<player::Player>)>,
+    players: Query<&Transform, With<player::Player>>,
     time: Res<Time>,
-    mut audio: audio::GameAudio,
-    game_assets: Res<assets::GameAssets>,
+    power_ups: Res<powerup::PowerUps>,
 ) {
-    for (mut player, p) in &mut player {
-        for (entity, _, mut food_transform) in &mut foods {
+    for p in &players {
+        for mut food_transform in &mut foods {
             food_transform.rotate_y(time.delta_seconds() * 1.2);
             food_transform.scale = Vec3::splat(1.0 + (time.elapsed_seconds().sin().abs() * 0.2));
 
-            if p.translation.distance(food_transform.translation) < 1.0 {
-                audio.play_sfx(&game_assets.collect);
-                floor_manager.score += 10;
-                player.donut_count += 1;
-                commands.entity(entity).despawn_recursive();
+            let to_player = p.translation - food_transform.translation;
+            if power_ups.is_active(powerup::PowerUp::Magnet) && to_player.length() < MAGNET_RADIUS {
+                food_transform.translation += to_player.clamp_length_max(MAGNET_SPEED * time.delta_seconds());
+            }
+        }
+    }
+}
+
+// rapier says when a player starts overlapping a donut's sensor. a donut
+// that turns up more than once before it's gone only gets picked up the once
+pub fn collect_food(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    foods: Query<&Food>,
+    players: Query<(), With<player::Player>>,
+    mut food_collected_event_writer: EventWriter<FoodCollectedEvent>,
+) {
+    let mut collected = vec!();
+    for collision_event in collision_events.iter() {
+        let (a, b) = match collision_event {
+            CollisionEvent::Started(a, b, _) => (*a, *b),
+            CollisionEvent::Stopped(..) => continue,
+        };
+        let (collector, entity) = if players.contains(a) { (a, b) } else { (b, a) };
+        if !players.contains(collector) || collected.contains(&entity) {
+            continue;
+        }
+
+        if let Ok(food) = foods.get(entity) {
+            commands.entity(entity).despawn_recursive();
+            collected.push(entity);
+            food_collected_event_writer.send(FoodCollectedEvent {
+                collector,
+                food: entity,
+                kind: food.kind,
+                group: food.group,
+            });
+        }
+    }
+}
+
+// points with the combo multiplier, the collector's tally and pattern bonuses.
+// after cull_food so a pattern that's just lost a donut doesn't get one
+pub fn score_food(
+    mut food_collected_events: EventReader<FoodCollectedEvent>,
+    mut players: Query<&mut player::Player>,
+    mut floor_manager: ResMut<floor::FloorManager>,
+    collectibles: Res<collectible::Collectibles>,
+    mut combo: ResMut<combo::Combo>,
+    mut pattern_groups: ResMut<PatternGroups>,
+) {
+    for event in food_collected_events.iter() {
+        let multiplier = combo.collect();
+        floor_manager.score += collectibles.points(event.kind) * multiplier;
+        if let Ok(mut player) = players.get_mut(event.collector) {
+            player.donut_count += 1;
+            if player.collected.len() <= event.kind {
+                player.collected.resize(event.kind + 1, 0);
             }
+            player.collected[event.kind] += 1;
+        }
+
+        if event.group.is_some_and(|group| pattern_groups.collect(group)) {
+            floor_manager.score += PATTERN_BONUS;
         }
     }
 }
 
+// after score_food so the pitch goes up with the combo it just counted
+pub fn play_food_sound(
+    mut food_collected_events: EventReader<FoodCollectedEvent>,
+    mut audio: audio::GameAudio,
+    game_assets: Res<assets::GameAssets>,
+    combo: Res<combo::Combo>,
+) {
+    for _ in food_collected_events.iter() {
+        audio.play_sfx_pitched(&game_assets.collect, combo.pitch());
+    }
+}
+
+// donuts the camera has gone past, going by shift_floors, were missed and
+// fail their pattern. ones drawn with the donut mesh go back in the pool,
+// scenes are despawned
+pub fn cull_food(
+    mut commands: Commands,
+    foods: Query<(Entity, &Food, &Transform, Option<&Handle<Scene>>)>,
+    cameras: Query<&Transform, With<Camera3d>>,
+    mut food_collected_events: EventReader<FoodCollectedEvent>,
+    mut food_stats: ResMut<FoodStats>,
+    mut pattern_groups: ResMut<PatternGroups>,
+) {
+    // collect_food has already despawned these
+    let collected = food_collected_events.iter().map(|event| event.food).collect::<Vec<_>>();
+    let camera_x = cameras.iter().map(|camera| camera.translation.x).fold(f32::MIN, f32::max);
+    for (entity, food, transform, scene) in &foods {
+        if transform.translation.x >= camera_x || collected.contains(&entity) {
+            continue;
+        }
+
+        food_stats.missed += 1;
+        if let Some(group) = food.group {
+            pattern_groups.miss(group);
+        }
+        if scene.is_some() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            commands.entity(entity)
+                .remove::<(Food, Collider)>()
+                .insert((PooledFood, Visibility::Hidden));
+        }
+    }
+}
+
+// every donut shares this one mesh
+pub fn donut_mesh() -> Mesh {
+    Mesh::from(
+        shape::Torus {
+            radius: 0.5,
+            ring_radius: 0.25,
+            subdivisions_segments: 8,
+            subdivisions_sides: 6,
+        }
+    )
+}
+
 pub fn spawn_food(
     mut commands: Commands,
     mut event_reader: EventReader<SpawnFoodEvent>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
     floor_manager: Res<floor::FloorManager>,
+    mut run_rng: ResMut<rng::RunRng>,
+    game_assets: Res<assets::GameAssets>,
+    players: Query<(&player::Player, &Transform)>,
+    collectibles: Res<collectible::Collectibles>,
+    gltfs: Res<Assets<Gltf>>,
+    pooled: Query<Entity, With<PooledFood>>,
+    jump_settings: Res<player::jump::JumpSettings>,
+    mut pattern_groups: ResMut<PatternGroups>,
 ) {
-    for _ in event_reader.iter() {
-        let (closest_left, farthest_right) = floor_manager.current_level_size();
-        let last_quarter = closest_left.x + ((farthest_right.x - closest_left.x) * 0.75);
-        let x = random_in_f32_range(last_quarter, farthest_right.x);
-        let z = random_in_f32_range(closest_left.y, farthest_right.y);
-        let (_, highest) = floor_manager.current_level_heights();
-
-        commands
-            .spawn((
+    let mut pool = pooled.iter();
+    let player = players.iter().next();
+    let jump = player.map(|(player, _)| player::JumpModel::new(player, &jump_settings));
+    let mut reachability = player
+        .zip(jump.as_ref())
+        .map(|((_, transform), jump)| Reachability::new(&floor_manager, jump, transform.translation));
+    for event in event_reader.iter() {
+        let positions = event.position.map(|position| {
+            let start = Vec3::new(floor_manager.world_x(position.x), position.y, position.z);
+            match jump.as_ref() {
+                // placed ones keep to the height they were given
+                Some(jump) => event.pattern
+                    .layout(jump)
+                    .into_iter()
+                    .map(|(offset, height)| start + Vec3::new(offset.x, height.unwrap_or(0.0), offset.y))
+                    .collect(),
+                None => vec!(start),
+            }
+        }).or_else(|| {
+            reachable_pattern(reachability.as_mut()?, event.pattern, &mut run_rng)
+        }).or_else(|| {
+            // the pattern didn't fit anywhere so it's just the one donut
+            reachable_pattern(reachability.as_mut()?, pattern::DonutPattern::Single, &mut run_rng)
+        });
+
+        let positions = match positions {
+            Some(positions) => positions,
+            // better no donut than one the player can't get to
+            None if jump.is_some() => {
+                warn!("couldn't find anywhere reachable to put a donut");
+                continue;
+            },
+            // without a player there's nothing to reach it, anywhere will do
+            None => {
+                let (closest_left, farthest_right) = floor_manager.current_level_size();
+                let last_quarter = closest_left.x + ((farthest_right.x - closest_left.x) * 0.75);
+                let x = run_rng.random_in_f32_range(last_quarter, farthest_right.x);
+                let (left, right) = floor_manager.row_bounds(x);
+                let z = run_rng.random_in_f32_range(left, right);
+                let (_, highest) = floor_manager.current_level_heights();
+                vec!(Vec3::new(x, (highest * 0.75) + 0.5, z))
+            },
+        };
+
+        let group = if positions.len() > 1 {
+            Some(pattern_groups.start(positions.len()))
+        } else {
+            None
+        };
+
+        // a whole pattern is the one kind of donut
+        let kind = collectibles.pick(&mut run_rng);
+        let scene = collectibles.scene(kind, &gltfs);
+        for position in positions {
+            let transform = {
+                let mut t = Transform::from_translation(position);
+                t.rotate_z(TAU * 0.25);
+                t
+            };
+            // scenes have their own children so only mesh donuts come out of the pool
+            let mut food = match scene.is_none().then(|| pool.next()).flatten() {
+                Some(entity) => {
+                    let mut food = commands.entity(entity);
+                    food.remove::<PooledFood>();
+                    food
+                },
+                None => commands.spawn_empty(),
+            };
+            food.insert((
                 CleanupMarker,
-                Food {},
-                PbrBundle {
-                    mesh: meshes.add(Mesh::from(
-                      shape::Torus { 
-                          radius: 0.5,
-                          ring_radius: 0.25,
-                          subdivisions_segments: 8,
-                          subdivisions_sides: 6,
-                      }
-                    )),
-                    material: materials.add(Color::hex(FOOD_COLOR).unwrap().into()),
-                    transform: {
-                        let mut t = Transform::from_xyz(x, (highest * 0.75) + 0.5, z);
-                        t.rotate_z(TAU * 0.25);
-                        t
-                    },
-                    ..default()
-                }
+                Food { group, kind },
+                Collider::ball(PICKUP_DISTANCE - player::PLAYER_HALF_SIZE),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+                // donuts have no body and the player is kinematic, which rapier skips by default
+                ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC,
             ));
+            match &scene {
+                Some(scene) => {
+                    food.insert(SceneBundle {
+                        scene: scene.clone(),
+                        transform,
+                        ..default()
+                    });
+                },
+                None => {
+                    food.insert(PbrBundle {
+                        mesh: game_assets.donut_mesh.clone(),
+                        material: collectibles.material(kind).unwrap_or_default(),
+                        transform,
+                        ..default()
+                    });
+                },
+            }
+        }
+    }
+}
+
+// somewhere in the last quarter of the spawned track the whole pattern fits
+// and the player can get to every donut in it, if one turns up
+fn reachable_pattern(
+    reachability: &mut Reachability,
+    pattern: pattern::DonutPattern,
+    run_rng: &mut rng::RunRng,
+) -> Option<Vec<Vec3>> {
+    let floor_manager = reachability.floor_manager;
+    let layout = pattern.layout(reachability.jump);
+    let length = layout.iter().map(|(offset, _)| offset.x).fold(0.0, f32::max);
+    let (first_x, last_x) = floor_manager.spawned_size();
+    let last_quarter = first_x + ((last_x - first_x) * 0.75);
+    (0..PLACEMENT_TRIES).find_map(|_| {
+        let x = run_rng.random_in_f32_range(last_quarter - length, last_x - length);
+        let x = (x / floor::FLOOR_CUBE_SIZE).round() * floor::FLOOR_CUBE_SIZE;
+        let profile = floor_manager.column_profile(x);
+        if profile.is_empty() {
+            return None;
+        }
+
+        let (z, top) = profile[run_rng.gen_range(0..profile.len())];
+        let start = Vec3::new(x, top + player::PLAYER_HALF_SIZE + DONUT_CLEARANCE, z);
+        let positions = layout
+            .iter()
+            .map(|(offset, height)| {
+                let (x, z) = (start.x + offset.x, start.z + offset.y);
+                let y = match height {
+                    Some(height) => start.y + height,
+                    None => floor_manager.height_at(x, z)? + player::PLAYER_HALF_SIZE + DONUT_CLEARANCE,
+                };
+                Some(Vec3::new(x, y, z))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        if positions.iter().all(|position| reachability.is_reachable(*position)) {
+            Some(positions)
+        } else {
+            None
+        }
+    })
+}
+
+// which blocks the player can get onto from where it is now. it's worked out
+// backwards from whatever gets asked about and remembered, since the floor
+// doesn't change while donuts are being placed
+pub struct Reachability<'a> {
+    floor_manager: &'a floor::FloorManager,
+    jump: &'a player::JumpModel,
+    // where the player is now, blocks up to here are already behind it
+    start: Vec3,
+    known: HashMap<(i32, i32), bool>,
+}
+
+impl<'a> Reachability<'a> {
+    pub fn new(floor_manager: &'a floor::FloorManager, jump: &'a player::JumpModel, start: Vec3) -> Self {
+        Reachability {
+            floor_manager,
+            jump,
+            start,
+            known: HashMap::new(),
+        }
+    }
+
+    // whether the player could pick up a donut at position. there has to be a
+    // block near enough under it to run over or jump from, and the player has
+    // to be able to get onto that block
+    pub fn is_reachable(&mut self, position: Vec3) -> bool {
+        let reach = (PICKUP_DISTANCE / floor::FLOOR_CUBE_SIZE).ceil() as i32;
+        for row in -reach..=reach {
+            for column in -reach..=reach {
+                let x = position.x + (row as f32 * floor::FLOOR_CUBE_SIZE);
+                let z = position.z + (column as f32 * floor::FLOOR_CUBE_SIZE);
+                let top = match self.floor_manager.height_at(x, z) {
+                    Some(top) => top,
+                    None => continue,
+                };
+
+                // the middle of the player goes from standing on the block to the top of a jump
+                let standing = top + player::PLAYER_HALF_SIZE;
+                let vertical = if position.y < standing {
+                    standing - position.y
+                } else {
+                    (position.y - standing - self.jump.rise).max(0.0)
+                };
+                let horizontal = Vec2::new(x - position.x, z - position.z).length();
+                if horizontal.hypot(vertical) < PICKUP_DISTANCE && self.can_get_onto(x, z, top) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    // jumping onto it from where the player is now, or stepping or jumping
+    // onto it from a block behind that the player can get onto itself
+    fn can_get_onto(&mut self, x: f32, z: f32, top: f32) -> bool {
+        if x <= self.start.x + floor::FLOOR_CUBE_SIZE {
+            return true;
+        }
+
+        let cell = ((x / floor::FLOOR_CUBE_SIZE).round() as i32, (z / floor::FLOOR_CUBE_SIZE).round() as i32);
+        if let Some(known) = self.known.get(&cell) {
+            return *known;
+        }
+
+        // it might be in the air already, over a gap with nothing behind it
+        let from_start = ((x - self.start.x) / floor::FLOOR_CUBE_SIZE).round() as i32;
+        let start_x = x - (from_start as f32 * floor::FLOOR_CUBE_SIZE);
+        let start_height = self.start.y - player::PLAYER_HALF_SIZE;
+        let reachable = (from_start <= self.rows_back() && self.can_jump_across(start_x, z, start_height, from_start, z, top))
+            || self.can_get_onto_from_behind(x, z, top);
+        self.known.insert(cell, reachable);
+        reachable
+    }
+
+    fn can_get_onto_from_behind(&mut self, x: f32, z: f32, top: f32) -> bool {
+        for back in 1..=self.rows_back() {
+            let behind_x = x - (back as f32 * floor::FLOOR_CUBE_SIZE);
+            for side in -1..=1 {
+                let behind_z = z + (side as f32 * floor::FLOOR_CUBE_SIZE);
+                if let Some(behind) = self.floor_manager.height_at(behind_x, behind_z) {
+                    if self.can_jump_across(behind_x, behind_z, behind, back, z, top)
+                    && self.can_get_onto(behind_x, behind_z, behind) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    // whether a jump from the block back rows behind clears the tops of
+    // everything on the way without running into the side of the one at the
+    // end. it can be taken anywhere from the middle of that block to as late
+    // as coyote time allows
+    fn can_jump_across(&self, behind_x: f32, behind_z: f32, behind: f32, back: i32, z: f32, top: f32) -> bool {
+        if top - behind > self.jump.climb() {
+            return false;
+        }
+        // right up against a ledge it can jump straight up it
+        if back == 1 {
+            return true;
+        }
+
+        // the tallest block in every row on the way, over the columns it crosses
+        let columns = ((z - behind_z).abs() / floor::FLOOR_CUBE_SIZE).round() as i32;
+        let walls = (1..back)
+            .filter_map(|row| {
+                let row_x = behind_x + (row as f32 * floor::FLOOR_CUBE_SIZE);
+                let tallest = (0..=columns)
+                    .filter_map(|column| {
+                        let column_z = behind_z.min(z) + (column as f32 * floor::FLOOR_CUBE_SIZE);
+                        self.floor_manager.height_at(row_x, column_z)
+                    })
+                    .reduce(f32::max)?;
+                Some((row as f32 * floor::FLOOR_CUBE_SIZE, tallest))
+            })
+            .collect::<Vec<_>>();
+
+        let edge = self.takeoff_edge();
+        (0..=TAKEOFF_SAMPLES).any(|sample| {
+            let takeoff = (edge + self.jump.coyote_reach()) * (sample as f32 / TAKEOFF_SAMPLES as f32);
+            let jumped_from = behind - self.jump.drop_over(takeoff - edge);
+            // the bottom of the player some way along from the middle of the block behind
+            let bottom = |along: f32| {
+                if along < takeoff {
+                    behind - self.jump.drop_over(along - edge)
+                } else {
+                    jumped_from + self.jump.height_at(along - takeoff)
+                }
+            };
+
+            // over a row from when the front of the player gets to it to when the back leaves
+            let cleared = walls.iter().all(|(along, tallest)| {
+                let (near, far) = (along - edge, along + edge);
+                [near, far, takeoff.clamp(near, far)].into_iter().all(|along| bottom(along) >= *tallest)
+            });
+            let landing = (back as f32 * floor::FLOOR_CUBE_SIZE) - edge;
+            cleared && bottom(landing) + player::STEP_HEIGHT >= top
+        })
+    }
+
+    // as far back as a jump could have come from
+    fn rows_back(&self) -> i32 {
+        let farthest = self.jump.reach() + self.takeoff_edge() + self.jump.coyote_reach();
+        (farthest / floor::FLOOR_CUBE_SIZE).ceil() as i32
+    }
+
+    // the player's still on a block until the back of it has gone over the edge
+    fn takeoff_edge(&self) -> f32 {
+        (floor::FLOOR_CUBE_SIZE / 2.0) + player::PLAYER_HALF_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::jump::JumpSettings;
+
+    // how tall the ordinary blocks are, in half-cubes like Floor::height
+    static FLAT: f32 = 2.0;
+    // far more than any jump gets up
+    static TALL: f32 = FLAT + 40.0;
+
+    // rows from 0 on with the player at row 0, each row all one height or a gap
+    fn floor(rows: i32, height: impl Fn(i32) -> Option<f32>) -> floor::FloorManager {
+        floor::FloorManager::from_heights((0..rows).flat_map(|row| {
+            let height = height(row);
+            (-2..=2).filter_map(move |column| Some(((row, column), height?)))
+        }))
+    }
+
+    fn jump() -> player::JumpModel {
+        let player = player::Player::new(&mut rng::RunRng::from_seed(1));
+        player::JumpModel::new(&player, &JumpSettings::default())
+    }
+
+    // whether a donut where spawn_food would put it over row is reachable
+    fn reachable_over(floor_manager: &floor::FloorManager, row: i32) -> bool {
+        let jump = jump();
+        let x = row as f32 * floor::FLOOR_CUBE_SIZE;
+        let top = floor_manager.height_at(x, 0.0).unwrap();
+        let position = Vec3::new(x, top + player::PLAYER_HALF_SIZE + DONUT_CLEARANCE, 0.0);
+        let start = Vec3::new(0.0, floor_manager.height_at(0.0, 0.0).unwrap() + player::PLAYER_HALF_SIZE, 0.0);
+        Reachability::new(floor_manager, &jump, start).is_reachable(position)
+    }
+
+    #[test]
+    fn pattern_finishes_with_its_last_donut() {
+        let mut pattern_groups = PatternGroups::default();
+        let group = pattern_groups.start(3);
+        assert!(!pattern_groups.collect(group));
+        assert!(!pattern_groups.collect(group));
+        assert!(pattern_groups.collect(group));
+        // and only the once
+        assert!(!pattern_groups.collect(group));
+    }
+
+    #[test]
+    fn missed_donut_fails_its_pattern() {
+        let mut pattern_groups = PatternGroups::default();
+        let missed = pattern_groups.start(2);
+        let other = pattern_groups.start(2);
+        assert!(!pattern_groups.collect(missed));
+        pattern_groups.miss(missed);
+        assert!(!pattern_groups.collect(missed));
+
+        assert!(!pattern_groups.collect(other));
+        assert!(pattern_groups.collect(other));
+    }
+
+    #[test]
+    fn flat_track_is_reachable() {
+        let floor_manager = floor(100, |_| Some(FLAT));
+        assert!(reachable_over(&floor_manager, 80));
+    }
+
+    #[test]
+    fn small_gaps_and_steps_are_reachable() {
+        let floor_manager = floor(100, |row| match row {
+            20..=23 => None,
+            40 => Some(FLAT + 2.0),
+            60.. => Some(FLAT + 4.0),
+            _ => Some(FLAT),
+        });
+        assert!(reachable_over(&floor_manager, 30));
+        assert!(reachable_over(&floor_manager, 50));
+        assert!(reachable_over(&floor_manager, 80));
+    }
+
+    #[test]
+    fn gap_too_wide_is_rejected() {
+        let jump = jump();
+        let gap = 20..70;
+        assert!(jump.reach() < gap.len() as f32 * floor::FLOOR_CUBE_SIZE);
+
+        let floor_manager = floor(100, |row| if gap.contains(&row) { None } else { Some(FLAT) });
+        assert!(reachable_over(&floor_manager, 10));
+        assert!(!reachable_over(&floor_manager, 75));
+        assert!(!reachable_over(&floor_manager, 95));
+    }
+
+    #[test]
+    fn step_too_high_is_rejected() {
+        let floor_manager = floor(100, |row| Some(if row < 30 { FLAT } else { TALL }));
+        assert!(reachable_over(&floor_manager, 20));
+        assert!(!reachable_over(&floor_manager, 30));
+        assert!(!reachable_over(&floor_manager, 80));
+    }
+
+    #[test]
+    fn wall_in_between_is_rejected() {
+        let floor_manager = floor(100, |row| Some(if row == 30 { TALL } else { FLAT }));
+        assert!(reachable_over(&floor_manager, 20));
+        assert!(!reachable_over(&floor_manager, 40));
+        assert!(!reachable_over(&floor_manager, 80));
+    }
+
+    #[test]
+    fn landing_past_a_wall_is_rejected() {
+        // low enough to clear with a jump from right up against it, so the
+        // only way past is the arc getting over it
+        let jump = jump();
+        let wall = FLAT + ((jump.climb() + 0.5) / (floor::FLOOR_CUBE_SIZE / 2.0)).ceil();
+        let gap = |wall: Option<f32>| floor(100, move |row| match row {
+            25..=29 | 31..=39 => None,
+            30 => wall,
+            _ => Some(FLAT),
+        });
+        assert!(reachable_over(&gap(None), 60));
+        assert!(!reachable_over(&gap(Some(wall)), 60));
     }
 }
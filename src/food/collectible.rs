@@ -0,0 +1,182 @@
+use bevy::asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset};
+use bevy::gltf::Gltf;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use rand::{distributions::WeightedIndex, prelude::Distribution};
+use serde::Deserialize;
+use crate::{assets, floor::palette::Palette, powerup::PowerUp, rng, FOOD_COLOR};
+
+pub static COLLECTIBLES_PATH: &str = "collectibles/donuts.collectibles.ron";
+
+#[derive(Deserialize, Clone, PartialEq, Default)]
+pub enum CollectibleModel {
+    // the plain donut mesh every donut used to be
+    #[default]
+    Donut,
+    // the first scene of a glTF under assets
+    Gltf(String),
+}
+
+#[derive(Deserialize, Clone)]
+pub struct CollectibleType {
+    pub name: String,
+    #[serde(default)]
+    pub model: CollectibleModel,
+    pub color: String,
+    pub points: usize,
+    // roughly one in this many donuts is this type, against the others
+    pub rarity: f32,
+    // a power-up that starts when it's picked up
+    #[serde(default)]
+    pub effect: Option<PowerUp>,
+}
+
+impl CollectibleType {
+    fn new(name: &str, color: &str, points: usize, rarity: f32, effect: Option<PowerUp>) -> Self {
+        CollectibleType {
+            name: name.to_string(),
+            model: CollectibleModel::Donut,
+            color: color.to_string(),
+            points,
+            rarity,
+            effect,
+        }
+    }
+}
+
+// every kind of donut, loaded from COLLECTIBLES_PATH so they can be
+// added to and tuned without rebuilding
+#[derive(Deserialize, TypeUuid, Clone)]
+#[uuid = "5d0e7c1a-8f43-4c52-9b6e-2a7f1c3d9e84"]
+pub struct CollectibleSet {
+    pub types: Vec<CollectibleType>,
+    // the glTF for each type that has one, loaded along with the set
+    #[serde(skip)]
+    models: Vec<Option<Handle<Gltf>>>,
+}
+
+impl CollectibleSet {
+    // what's used when the file is missing or doesn't load, asset_loading
+    // lets it fail rather than stopping the game
+    pub fn builtin() -> Self {
+        CollectibleSet {
+            types: vec!(
+                CollectibleType::new("Plain", FOOD_COLOR, 10, 1.0, None),
+                CollectibleType::new("Sprinkled", "ff6fb5", 25, 4.0, None),
+                CollectibleType::new("Golden", "ffd700", 50, 12.0, None),
+                CollectibleType::new("Rare", "7f5af0", 100, 40.0, Some(PowerUp::Boost)),
+                CollectibleType::new("Magnet", "e0e0e0", 10, 30.0, Some(PowerUp::Magnet)),
+                CollectibleType::new("Shield", "4cc9f0", 10, 35.0, Some(PowerUp::Shield)),
+                CollectibleType::new("Slow-Mo", "80ed99", 10, 35.0, Some(PowerUp::SlowMotion)),
+            ),
+            models: vec!(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CollectibleSetLoader;
+
+impl AssetLoader for CollectibleSetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let mut set = ron::de::from_bytes::<CollectibleSet>(bytes)?;
+            let mut dependencies = vec!();
+            set.models = set.types
+                .iter()
+                .map(|collectible_type| match &collectible_type.model {
+                    CollectibleModel::Donut => None,
+                    CollectibleModel::Gltf(path) => {
+                        let path = AssetPath::new(path.into(), None);
+                        dependencies.push(path.clone());
+                        Some(load_context.get_handle(path))
+                    },
+                })
+                .collect();
+            load_context.set_default_asset(LoadedAsset::new(set).with_dependencies(dependencies));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["collectibles.ron"]
+    }
+}
+
+// the set being played with this run, and what each type is drawn with
+#[derive(Resource, Default)]
+pub struct Collectibles {
+    pub types: Vec<CollectibleType>,
+    models: Vec<Option<Handle<Gltf>>>,
+    materials: Vec<Handle<StandardMaterial>>,
+    weights: Option<WeightedIndex<f32>>,
+}
+
+impl Collectibles {
+    // takes the types from the set if it loaded, otherwise the built in ones
+    pub fn start_run(
+        &mut self,
+        set: Option<&CollectibleSet>,
+        palette: &mut Palette,
+        materials: &mut Assets<StandardMaterial>,
+    ) {
+        let set = set.cloned().unwrap_or_else(CollectibleSet::builtin);
+        self.materials = set.types
+            .iter()
+            .map(|collectible_type| {
+                let color = Color::hex(&collectible_type.color).unwrap_or_else(|_| Color::hex(FOOD_COLOR).unwrap());
+                // the palette hands back the same material every run
+                palette.material(color, materials)
+            })
+            .collect();
+        self.weights = WeightedIndex::new(
+            set.types.iter().map(|collectible_type| 1.0 / collectible_type.rarity.max(1.0))
+        ).ok();
+        self.models = set.models;
+        self.types = set.types;
+    }
+
+    pub fn pick(&self, run_rng: &mut rng::RunRng) -> usize {
+        self.weights
+            .as_ref()
+            .map(|weights| weights.sample(run_rng))
+            .unwrap_or(0)
+    }
+
+    pub fn material(&self, kind: usize) -> Option<Handle<StandardMaterial>> {
+        self.materials.get(kind).cloned()
+    }
+
+    // the scene to draw a type with, if it has a model and it's finished loading
+    pub fn scene(&self, kind: usize, gltfs: &Assets<Gltf>) -> Option<Handle<Scene>> {
+        let model = self.models.get(kind)?.as_ref()?;
+        gltfs.get(model)?.scenes.first().cloned()
+    }
+
+    pub fn points(&self, kind: usize) -> usize {
+        self.types.get(kind).map(|collectible_type| collectible_type.points).unwrap_or(0)
+    }
+
+    pub fn effect(&self, kind: usize) -> Option<PowerUp> {
+        self.types.get(kind)?.effect
+    }
+
+    pub fn name(&self, kind: usize) -> &str {
+        self.types.get(kind).map(|collectible_type| collectible_type.name.as_str()).unwrap_or("Donut")
+    }
+}
+
+pub fn start_collectibles(
+    mut collectibles: ResMut<Collectibles>,
+    sets: Res<Assets<CollectibleSet>>,
+    game_assets: Res<assets::GameAssets>,
+    mut palette: ResMut<Palette>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    collectibles.start_run(sets.get(&game_assets.collectibles), &mut palette, &mut materials);
+}
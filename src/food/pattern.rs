@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::Distribution};
+use crate::{floor::FLOOR_CUBE_SIZE, player, rng};
+
+// rows between donuts in a line or zig-zag, a bit more than a donut across
+static DONUT_SPACING: f32 = 4.0;
+static LINE_LENGTH: usize = 5;
+static ARC_LENGTH: usize = 7;
+static ZIG_ZAG_LENGTH: usize = 6;
+// how many columns a zig-zag goes either side of where it started
+static ZIG_ZAG_SWING: f32 = 3.0;
+// rows and columns between donuts in a cluster
+static CLUSTER_SPACING: f32 = 2.0;
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DonutPattern {
+    #[default]
+    Single,
+    // one after another straight down the track
+    Line,
+    // along the path of a jump taken from the first one
+    Arc,
+    // back and forth across the columns
+    ZigZag,
+    // a bunch packed close together
+    Cluster,
+}
+
+pub static DEFAULT_PATTERN_WEIGHTS: [(DonutPattern, u32); 5] = [
+    (DonutPattern::Single, 3),
+    (DonutPattern::Line, 4),
+    (DonutPattern::Arc, 3),
+    (DonutPattern::ZigZag, 3),
+    (DonutPattern::Cluster, 1),
+];
+
+impl DonutPattern {
+    pub fn random(run_rng: &mut rng::RunRng) -> Self {
+        let index = WeightedIndex::new(DEFAULT_PATTERN_WEIGHTS.iter().map(|(_, weight)| *weight)).unwrap();
+        DEFAULT_PATTERN_WEIGHTS[index.sample(run_rng)].0
+    }
+
+    // where each donut goes from the first one, x along the track and z across it.
+    // an arc also says how far above the first one each donut is, everything
+    // else sits over whatever floor is under it
+    pub fn layout(self, jump: &player::JumpModel) -> Vec<(Vec2, Option<f32>)> {
+        let spacing = DONUT_SPACING * FLOOR_CUBE_SIZE;
+        match self {
+            DonutPattern::Single => vec!((Vec2::ZERO, None)),
+            DonutPattern::Line => {
+                (0..LINE_LENGTH)
+                    .map(|i| (Vec2::new(i as f32 * spacing, 0.0), None))
+                    .collect()
+            },
+            DonutPattern::Arc => {
+                (0..ARC_LENGTH)
+                    .map(|i| {
+                        let seconds = jump.air_time * (i as f32 / (ARC_LENGTH - 1) as f32);
+                        let position = jump.position_after(seconds);
+                        (Vec2::new(position.x, 0.0), Some(position.y))
+                    })
+                    .collect()
+            },
+            DonutPattern::ZigZag => {
+                (0..ZIG_ZAG_LENGTH)
+                    .map(|i| {
+                        let side = if i % 2 == 0 { -1.0 } else { 1.0 };
+                        (Vec2::new(i as f32 * spacing, side * ZIG_ZAG_SWING * FLOOR_CUBE_SIZE), None)
+                    })
+                    .collect()
+            },
+            DonutPattern::Cluster => {
+                let spacing = CLUSTER_SPACING * FLOOR_CUBE_SIZE;
+                (0..3)
+                    .flat_map(|row| (-1..=1).map(move |column| {
+                        (Vec2::new(row as f32 * spacing, column as f32 * spacing), None)
+                    }))
+                    .collect()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::jump::JumpSettings;
+
+    #[test]
+    fn arc_follows_a_jump() {
+        let player = player::Player::new(&mut rng::RunRng::from_seed(1));
+        let jump = player::JumpModel::new(&player, &JumpSettings::default());
+        let layout = DonutPattern::Arc.layout(&jump);
+        assert_eq!(layout.len(), ARC_LENGTH);
+
+        // every donut is right where the player is that far into the jump
+        for (offset, height) in &layout {
+            let height = height.expect("arc donuts have their own height");
+            assert!((jump.height_at(offset.x) - height).abs() < 0.05, "{:?} at {}", offset, height);
+        }
+
+        // from where it jumps, over the top and back down to where it jumped from
+        let (first, _) = layout[0];
+        let (last, last_height) = layout[ARC_LENGTH - 1];
+        assert!(first.x < FLOOR_CUBE_SIZE);
+        assert!(last_height.unwrap().abs() < 0.1);
+        let (_, highest) = layout[ARC_LENGTH / 2];
+        assert!((highest.unwrap() - jump.rise).abs() < 0.1);
+        // and no further than the jump actually goes along
+        assert!(last.x <= jump.reach());
+        assert!(last.x >= jump.speed * jump.air_time * 0.9);
+    }
+}
@@ -1,24 +1,45 @@
-use crate::{player};
+use crate::{floor, player::{self, state::{PlayerState, PlayerStateEvent}}};
 use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+// how much the camera leans toward the middle of the track instead of the player
+static CENTER_LINE_PULL: f32 = 0.5;
 
 pub fn follow_player(
     mut cameras: Query<&mut Transform, (With<Camera3d>,  Without<player::Player>)>,
-    players: Query<&Transform, (With<player::Player>, Without<Camera3d>)>,
+    players: Query<(Entity, &Transform), With<player::Player>>,
+    mut player_state_events: EventReader<PlayerStateEvent>,
+    // players dropping out of the track, they get watched going, not followed down
+    mut dropping: Local<HashSet<Entity>>,
+    floor_manager: Res<floor::FloorManager>,
     time: Res<Time>,
 ) {
+    for event in player_state_events.iter() {
+        if matches!(event.to, PlayerState::Dying | PlayerState::Dead) {
+            dropping.insert(event.entity);
+        } else {
+            dropping.remove(&event.entity);
+        }
+    }
+    dropping.retain(|entity| players.contains(*entity));
+
     let camera_speed = 20.0;
     for mut camera_transform in cameras.iter_mut() {
-        for player_transform in players.iter() {
-            camera_transform.translation.y += 
-                ((player_transform.translation.y + 0.75) - camera_transform.translation.y)
-                * (camera_speed * 0.25)
-                * time.delta_seconds();
+        for (entity, player_transform) in players.iter() {
+            if !dropping.contains(&entity) {
+                camera_transform.translation.y += 
+                    ((player_transform.translation.y + 0.75) - camera_transform.translation.y)
+                    * (camera_speed * 0.25)
+                    * time.delta_seconds();
+            }
             camera_transform.translation.x += 
                 ((player_transform.translation.x - 2.8) - camera_transform.translation.x)
                 * camera_speed
                 * time.delta_seconds();
+            let center_z = floor_manager.center_z(player_transform.translation.x);
+            let target_z = player_transform.translation.z + ((center_z - player_transform.translation.z) * CENTER_LINE_PULL);
             camera_transform.translation.z += 
-                (player_transform.translation.z - camera_transform.translation.z)
+                (target_z - camera_transform.translation.z)
                 * (camera_speed * 0.5)
                 * time.delta_seconds();
         }
@@ -1,4 +1,4 @@
-use crate::{AppState, asset_loading, assets, floor, ui::text_size, CleanupMarker, menus, player, audio};
+use crate::{AppState, asset_loading, assets, combo, floor, food, ui::text_size, CleanupMarker, menus, player, audio, rng};
 
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
@@ -8,8 +8,11 @@ impl Plugin for GameOverPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_system(handle_game_over.in_set(OnUpdate(AppState::InGame)))
+            .add_system(handle_level_complete.in_set(OnUpdate(AppState::InGame)))
             .add_system(update_game_over.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(update_game_over.in_set(OnUpdate(AppState::LevelComplete)))
             .add_system(despawn_ui.in_schedule(OnEnter(AppState::GameOver)))
+            .add_system(despawn_ui.in_schedule(OnEnter(AppState::LevelComplete)))
             .init_resource::<GameOverState>()
             .add_event::<GameOverEvent>();
     }
@@ -27,9 +30,12 @@ enum DisplayState {
     GameOver,
     BaseScore,
     DonutsCollected,
+    DonutTypes,
+    BestCombo,
     Distance,
     Height,
     Final,
+    Seed,
     Continue,
     Wait
 }
@@ -70,6 +76,11 @@ fn update_game_over(
     mut game_assets: ResMut<assets::GameAssets>,
     mut assets_handler: asset_loading::AssetsHandler,
     mut audio: audio::GameAudio,
+    run_rng: Res<rng::RunRng>,
+    app_state: Res<State<AppState>>,
+    collectibles: Res<food::collectible::Collectibles>,
+    combo: Res<combo::Combo>,
+    food_stats: Res<food::FoodStats>,
 ) {
     game_over_state.timer -= time.delta_seconds();
     game_over_state.timer = game_over_state.timer.clamp(-3.0, 3.0);
@@ -81,7 +92,7 @@ fn update_game_over(
     game_over_state.timer = COUNTDOWN;
     let (player, player_transform) = players.single();
     let final_score = (floor_manager.score * player.donut_count.max(1)) as f32
-        + (player_transform.translation.x * 10.0) 
+        + (floor_manager.track_x(player_transform.translation.x) * 10.0) 
         + (floor_manager.current_level_heights().1 * 100.0);
         
 
@@ -129,7 +140,7 @@ fn update_game_over(
                                 parent,
                                 game_assets.font.clone(),
                                 text_scaler.scale(menus::DEFAULT_FONT_SIZE * 1.6),
-                                "GAME OVER",
+                                if app_state.0 == AppState::LevelComplete { "COURSE COMPLETE" } else { "GAME OVER" },
                                 vec!(CleanupMarker), // just an empty vec since can't do <impl Trait>
                             );
                         });
@@ -172,7 +183,7 @@ fn update_game_over(
         },
         DisplayState::DonutsCollected => {
             audio.play_sfx(&game_assets.game_over);
-            game_over_state.display_state = DisplayState::Distance;
+            game_over_state.display_state = DisplayState::DonutTypes;
             for entity in &game_over_containers {
                 let child = commands.spawn(
                             NodeBundle {
@@ -197,7 +208,7 @@ fn update_game_over(
                                     parent,
                                     game_assets.font.clone(),
                                     text_scaler.scale(menus::DEFAULT_FONT_SIZE * 1.0),
-                                    &format!("Donuts: {}", player.donut_count),
+                                    &format!("Donuts: {} ({} missed)", player.donut_count, food_stats.missed),
                                     vec!(CleanupMarker), // just an empty vec since can't do <impl Trait>
                                 );
                             })
@@ -205,6 +216,37 @@ fn update_game_over(
                 commands.entity(entity).add_child(child);
             }
         },
+        DisplayState::DonutTypes => {
+            game_over_state.display_state = DisplayState::BestCombo;
+            let breakdown = (0..collectibles.types.len())
+                .map(|kind| format!("{} {}", collectibles.name(kind), player.collected.get(kind).copied().unwrap_or(0)))
+                .collect::<Vec<_>>()
+                .join("  ");
+            for entity in &game_over_containers {
+                spawn_stat_line(
+                    &mut commands,
+                    entity,
+                    game_assets.font.clone(),
+                    text_scaler.scale(menus::DEFAULT_FONT_SIZE * 0.7),
+                    "Donut Types",
+                    &breakdown,
+                );
+            }
+        },
+        DisplayState::BestCombo => {
+            audio.play_sfx(&game_assets.game_over);
+            game_over_state.display_state = DisplayState::Distance;
+            for entity in &game_over_containers {
+                spawn_stat_line(
+                    &mut commands,
+                    entity,
+                    game_assets.font.clone(),
+                    text_scaler.scale(menus::DEFAULT_FONT_SIZE * 1.0),
+                    "Best Combo",
+                    &combo.best,
+                );
+            }
+        },
         DisplayState::Distance => {
             audio.play_sfx(&game_assets.game_over);
             game_over_state.display_state = DisplayState::Height;
@@ -232,7 +274,7 @@ fn update_game_over(
                                     parent,
                                     game_assets.font.clone(),
                                     text_scaler.scale(menus::DEFAULT_FONT_SIZE * 1.0),
-                                    &format!("Distance: {:.2}", player_transform.translation.x),
+                                    &format!("Distance: {:.2}", floor_manager.track_x(player_transform.translation.x)),
                                     vec!(CleanupMarker), // just an empty vec since can't do <impl Trait>
                                 );
                             })
@@ -277,7 +319,7 @@ fn update_game_over(
         },
         DisplayState::Final => {
             audio.play_sfx(&game_assets.game_over);
-            game_over_state.display_state = DisplayState::Continue;
+            game_over_state.display_state = DisplayState::Seed;
             for entity in &game_over_containers {
                 let child = commands.spawn(
                             NodeBundle {
@@ -310,6 +352,19 @@ fn update_game_over(
                 commands.entity(entity).add_child(child);
             }
         },
+        DisplayState::Seed => {
+            game_over_state.display_state = DisplayState::Continue;
+            for entity in &game_over_containers {
+                spawn_stat_line(
+                    &mut commands,
+                    entity,
+                    game_assets.font.clone(),
+                    text_scaler.scale(menus::DEFAULT_FONT_SIZE * 0.6),
+                    "Seed",
+                    &run_rng.seed,
+                );
+            }
+        },
         DisplayState::Continue => {
             game_over_state.timer = -1.0;
             game_over_state.display_state = DisplayState::Wait;
@@ -368,6 +423,57 @@ fn handle_game_over(
     }
 }
 
+fn handle_level_complete(
+    mut event_reader: EventReader<floor::level::LevelCompleteEvent>,
+    mut game_assets: ResMut<assets::GameAssets>,
+    mut assets_handler: asset_loading::AssetsHandler,
+) {
+    if !event_reader.is_empty() {
+        event_reader.clear();
+        assets_handler.load(AppState::LevelComplete, &mut game_assets);
+    }
+}
+
+// one more line under the title, "label: value" centred across the screen
+fn spawn_stat_line(
+    commands: &mut Commands,
+    container: Entity,
+    font: Handle<Font>,
+    font_size: f32,
+    label: &str,
+    value: &dyn std::fmt::Display,
+) {
+    let child = commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(10.0)),
+                position_type: PositionType::Relative,
+                justify_content: JustifyContent::Center,
+                margin: UiRect {
+                    left: Val::Auto,
+                    right: Val::Auto,
+                    ..default()
+                },
+                align_items: AlignItems::Center,
+                flex_direction: FlexDirection::Row,
+                ..Default::default()
+            },
+            background_color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            add_title(
+                parent,
+                font,
+                font_size,
+                &format!("{}: {}", label, value),
+                vec!(CleanupMarker), // just an empty vec since can't do <impl Trait>
+            );
+        })
+        .id();
+    commands.entity(container).add_child(child);
+}
+
 pub fn add_title(
     builder: &mut ChildBuilder<'_, '_, '_>,
     font: Handle<Font>,
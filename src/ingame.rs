@@ -2,6 +2,7 @@ use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 use bevy::gltf::Gltf;
 use crate::{
+    difficulty,
     direction,
     player,
     game_camera,
@@ -14,6 +15,7 @@ PLAYER_COLOR,
     ZeroSignum,
     cleanup,
     CleanupMarker,
+    rng,
 };
 
 pub struct InGamePlugin;
@@ -33,19 +35,29 @@ impl Plugin for InGamePlugin {
             )
             .add_system(setup.in_schedule(OnEnter(AppState::InGame)))
             .add_systems((
-                    food::spawn_food,
+                    // after the origin moves so positions come out right
+                    food::spawn_food.after(floor::origin::shift_origin),
                     food::update_food,
+                    food::collect_food,
+                    food::score_food.after(food::collect_food).after(food::cull_food),
+                    food::play_food_sound.after(food::score_food),
+                    food::cull_food.after(food::collect_food).after(floor::shift_floors),
                 )
                 .in_set(OnUpdate(AppState::InGame))
             )
             .add_systems((
                     player::handle_input, 
+                    floor::platform::move_platforms,
                     player::move_player,
                     player::spin_mesh,
                     floor::update_floors,
+                    floor::kind::crumble_floors,
+                    floor::kind::press_floors,
                     game_camera::follow_player,
                     floor::shift_floors,
-                    apply_system_buffers
+                    floor::level::check_finish_line,
+                    apply_system_buffers,
+                    floor::origin::shift_origin,
                 ).chain()
                 .in_set(OnUpdate(AppState::InGame))
             );
@@ -62,6 +74,19 @@ pub fn load(
     assets_handler.add_audio(&mut game_assets.game_over, "audio/game_over.wav");
     assets_handler.add_audio(&mut game_assets.game_over, "audio/game_over.wav");
     assets_handler.add_glb(&mut game_assets.TJ, "models/tj.glb");
+    assets_handler.add_difficulty(&mut game_assets.difficulty, difficulty::DIFFICULTY_PATH);
+    assets_handler.add_collectibles(&mut game_assets.collectibles, food::collectible::COLLECTIBLES_PATH);
+    assets_handler.add_standard_mesh(&mut game_assets.donut_mesh, food::donut_mesh());
+
+    if assets_handler.track_settings.mode == floor::track::TrackMode::Heightmap {
+        let path = assets_handler.track_settings.heightmap.path.clone();
+        assets_handler.add_image(&mut game_assets.track_heightmap, &path);
+    }
+
+    if assets_handler.track_settings.mode == floor::track::TrackMode::Level {
+        let path = assets_handler.track_settings.level.path.clone();
+        assets_handler.add_level(&mut game_assets.level, &path);
+    }
 }
 
 fn setup(
@@ -69,27 +94,39 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut floor_manager: ResMut<floor::FloorManager>,
+    mut difficulty: ResMut<difficulty::DifficultyProfile>,
+    difficulty_configs: Res<Assets<difficulty::DifficultyConfig>>,
+    mut palette: ResMut<floor::palette::Palette>,
     mut clear_color: ResMut<ClearColor>,
     mut food_spawn_event_writer: EventWriter<food::SpawnFoodEvent>,
+    mut run_rng: ResMut<rng::RunRng>,
+    track_settings: Res<floor::track::TrackSettings>,
+    images: Res<Assets<Image>>,
+    levels: Res<Assets<floor::level::Level>>,
     asset_server: Res<AssetServer>,
     game_assets: Res<assets::GameAssets>,
     assets_gltf: Res<Assets<Gltf>>,
 ) {
     clear_color.0 = Color::hex(BACKGROUND_COLOR).unwrap();
-    floor::setup_floor(&mut commands, &mut meshes, &mut materials, &mut floor_manager, &mut food_spawn_event_writer);
+    run_rng.start_run();
+    info!("Seed: {}", run_rng.seed);
+    difficulty.start_run(difficulty_configs.get(&game_assets.difficulty));
+    floor::setup_floor(&mut commands, &mut meshes, &mut materials, &mut palette, &mut floor_manager, &mut run_rng, track_settings.generator(&game_assets, &images, &levels), track_settings.collider_mode, &difficulty, &mut food_spawn_event_writer);
 
     if let Some(gltf) = assets_gltf.get(&game_assets.TJ) {
         commands
             .spawn((
                 RigidBody::KinematicPositionBased,
-                Collider::cuboid(0.25, 0.25, 0.25),
+                Collider::cuboid(player::PLAYER_HALF_SIZE, player::PLAYER_HALF_SIZE, player::PLAYER_HALF_SIZE),
                 CleanupMarker,
                 ColliderMassProperties::Density(2.0),
                 KinematicCharacterController {
                     translation: Some(Vec3::new(0.0, 0.5, 0.0)),
                     offset: CharacterLength::Absolute(0.01),
+                    // donut sensors are for picking up, not running into
+                    filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
                     autostep: Some(CharacterAutostep {
-                        max_height: CharacterLength::Absolute(1.0),
+                        max_height: CharacterLength::Absolute(player::STEP_HEIGHT),
                         min_width: CharacterLength::Absolute(0.05),
                         include_dynamic_bodies: true,
                     }),
@@ -103,7 +140,7 @@ fn setup(
                 local: Transform::from_xyz(0.0, 0.5, 0.0),
                 ..default()
             },
-            player::PlayerBundle::new(),
+            player::PlayerBundle::new(&mut run_rng),
         )).with_children(|parent| {
             parent.spawn((SceneBundle { scene: gltf.scenes[0].clone(), ..default() }, player::InnerMesh));
         });
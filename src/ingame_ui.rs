@@ -1,7 +1,8 @@
 use crate::{
-    assets::GameAssets, menus, AppState, ui::text_size, ingame, floor,CleanupMarker, player,
+    assets::GameAssets, menus, AppState, ui::text_size, ingame, floor,CleanupMarker, player, powerup, combo,
 };
 use bevy::prelude::*;
+use bevy::utils::HashSet;
 
 pub struct InGameUIPlugin;
 impl Plugin for InGameUIPlugin {
@@ -31,22 +32,54 @@ fn update_game_over_ui(
 fn update_ui(
     floor_manager: Res<floor::FloorManager>,
     game_assets: Res<GameAssets>,
-    mut score_indicators: Query<&mut Text, (With<ScoreIndicator>, Without<DeathIndicator>)>,
-    mut death_indicators: Query<&mut Text, (With<DeathIndicator>, Without<ScoreIndicator>)>,
+    mut score_indicators: Query<&mut Text, (With<ScoreIndicator>, Without<DeathIndicator>, Without<PowerUpIndicator>, Without<ComboIndicator>)>,
+    mut death_indicators: Query<&mut Text, (With<DeathIndicator>, Without<ScoreIndicator>, Without<PowerUpIndicator>, Without<ComboIndicator>)>,
+    mut power_up_indicators: Query<&mut Text, (With<PowerUpIndicator>, Without<ScoreIndicator>, Without<DeathIndicator>, Without<ComboIndicator>)>,
+    mut combo_indicators: Query<&mut Text, (With<ComboIndicator>, Without<ScoreIndicator>, Without<DeathIndicator>, Without<PowerUpIndicator>)>,
     player: Query<&player::Player>,
+    mut player_state_events: EventReader<player::state::PlayerStateEvent>,
+    // players the stall countdown is running for
+    mut stalled: Local<HashSet<Entity>>,
+    power_ups: Res<powerup::PowerUps>,
+    combo: Res<combo::Combo>,
 ) {
+    for event in player_state_events.iter() {
+        if event.to == player::state::PlayerState::Stalled {
+            stalled.insert(event.entity);
+        } else if event.from == player::state::PlayerState::Stalled {
+            stalled.remove(&event.entity);
+        }
+    }
+    stalled.retain(|entity| player.contains(*entity));
+
     for mut score in &mut score_indicators {
         score.sections[0].value = format!("{}", floor_manager.score);
     }
 
-    for p in &player {
-        for mut death in &mut death_indicators {
-            if let Some(death_timer) = p.death_timer {
-                death.sections[0].value = format!("{:.2}", death_timer);
-            } else {
-                death.sections[0].value = format!("");
-            }
-        }
+    for mut combo_indicator in &mut combo_indicators {
+        combo_indicator.sections[0].value = if combo.streak > 1 {
+            format!("  x{} ({} in a row)", combo.multiplier(), combo.streak)
+        } else {
+            String::new()
+        };
+    }
+
+    for mut power_up_indicator in &mut power_up_indicators {
+        power_up_indicator.sections[0].value = power_ups
+            .active()
+            .map(|(power_up, left)| format!("{} {:.1}", power_up.name(), left))
+            .collect::<Vec<_>>()
+            .join("  ");
+    }
+
+    let countdown = stalled
+        .iter()
+        .find_map(|entity| player.get(*entity).ok()?.death_timer);
+    for mut death in &mut death_indicators {
+        death.sections[0].value = match countdown {
+            Some(death_timer) => format!("{:.2}", death_timer),
+            None => String::new(),
+        };
     }
 }
 
@@ -105,6 +138,42 @@ fn setup(
                         "0%",
                         vec!(ScoreIndicator), // just an empty vec since can't do <impl Trait>
                     );
+                    add_title(
+                        parent,
+                        game_assets.font.clone(),
+                        text_scaler.scale(menus::DEFAULT_FONT_SIZE * 0.6),
+                        "",
+                        vec!(ComboIndicator), // just an empty vec since can't do <impl Trait>
+                    );
+                });
+
+            // whatever power-ups are going and how long they've got left
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(50.0), Val::Percent(15.0)),
+                        position_type: PositionType::Relative,
+                        justify_content: JustifyContent::FlexEnd,
+                        margin: UiRect {
+                            left: Val::Auto,
+                            right: Val::Percent(2.0),
+                            ..default()
+                        },
+                        align_items: AlignItems::Center,
+                        flex_direction: FlexDirection::Row,
+                        ..Default::default()
+                    },
+                    background_color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    add_title(
+                        parent,
+                        game_assets.font.clone(),
+                        text_scaler.scale(menus::DEFAULT_FONT_SIZE * 0.6),
+                        "",
+                        vec!(PowerUpIndicator), // just an empty vec since can't do <impl Trait>
+                    );
                 });
 
             parent
@@ -142,6 +211,10 @@ fn setup(
 struct ScoreIndicator;
 #[derive(Component)]
 struct DeathIndicator;
+#[derive(Component)]
+struct PowerUpIndicator;
+#[derive(Component)]
+struct ComboIndicator;
 
 pub fn add_title(
     builder: &mut ChildBuilder<'_, '_, '_>,
@@ -2,7 +2,6 @@
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 use bevy_rapier3d::prelude::*;
-use rand::{thread_rng, Rng};
 use bevy::winit::WinitSettings;
 use bevy_rapier3d::render::RapierDebugRenderPlugin;
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
@@ -10,7 +9,11 @@ use bevy_inspector_egui::{quick::WorldInspectorPlugin, bevy_egui};
 
 mod asset_loading;
 mod assets;
+mod bench;
+mod combo;
+mod difficulty;
 mod player;
+mod powerup;
 mod audio;
 mod floor;
 mod food;
@@ -20,11 +23,19 @@ mod game_over;
 mod ingame;
 mod ingame_ui;
 mod menus;
+mod rng;
 mod splash;
 mod title_screen;
 mod ui;
 
 fn main() {
+  let run_args = RunArgs::from_args();
+  if run_args.bench_floors {
+      bench::run();
+      return;
+  }
+  let run_rng = rng::RunRng::from_run_args(&run_args);
+
   App::new()
         .add_plugins(DefaultPlugins.set(AssetPlugin {
           ..default()
@@ -37,6 +48,7 @@ fn main() {
           ..default()
         }))
         .add_state::<AppState>()
+        .insert_resource(run_args)
 //        .add_plugin(WorldInspectorPlugin::new())
         .insert_resource(bevy_egui::EguiSettings { scale_factor: 1.8, ..default() })
 //      .add_plugin(LogDiagnosticsPlugin::default())
@@ -44,6 +56,7 @@ fn main() {
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
 //        .add_plugin(RapierDebugRenderPlugin::default())
         .add_plugin(floor::FloorPlugin)
+        .add_plugin(difficulty::DifficultyPlugin)
         .add_plugin(ingame::InGamePlugin)
         .add_plugin(audio::GameAudioPlugin)
         .add_plugin(ingame_ui::InGameUIPlugin)
@@ -55,7 +68,11 @@ fn main() {
         .add_plugin(ui::text_size::TextSizePlugin)
         .add_plugin(assets::AssetsPlugin)
         .add_plugin(food::FoodPlugin)
+        .add_plugin(powerup::PowerUpPlugin)
+        .add_plugin(combo::ComboPlugin)
+        .insert_resource(run_rng)
         .add_startup_system(window_settings)
+        .add_startup_system(warn_run_args)
         .add_system(bootstrap.in_set(OnUpdate(AppState::Initial)))
         .add_system(debug)
         .run();
@@ -69,10 +86,110 @@ pub enum AppState {
     Splash,
     TitleScreen,
     GameOver,
+    LevelComplete,
     Reset,
     InGame,
 }
 
+// everything that can be set from the command line. it's read once at
+// startup and each plugin takes the parts it cares about from here
+#[derive(Resource, Default, Clone)]
+pub struct RunArgs {
+    pub bench_floors: bool,
+    pub seed: Option<u64>,
+    pub track: Option<floor::track::TrackMode>,
+    pub heightmap: Option<String>,
+    pub level: Option<String>,
+    pub colliders: Option<floor::ColliderMode>,
+    pub floor_kinds: Option<f32>,
+    pub platforms: Option<f32>,
+    pub track_shapes: Option<f32>,
+    pub difficulty: Option<difficulty::Preset>,
+    pub extra_jumps: Option<usize>,
+    pub coyote_time: Option<f32>,
+    pub jump_buffer: Option<f32>,
+    pub jump_hold: Option<f32>,
+    pub jump_cut: Option<f32>,
+    // anything that didn't make sense, it's read before logging is set up
+    // so warn_run_args says so once the app's running
+    problems: Vec<String>,
+}
+
+impl RunArgs {
+    // "--bench-floors", "--seed <number>",
+    // "--track <flat|segments|noise|heightmap|level>", "--heightmap <path under assets>",
+    // "--level <path under assets>", "--colliders <cuboids|heightfield>",
+    // "--floor-kinds <density>", "--platforms <density>", "--track-shapes <density>",
+    // "--difficulty <easy|normal|hard>", "--extra-jumps <n>", "--coyote-time <seconds>",
+    // "--jump-buffer <seconds>", "--jump-hold <seconds>" and "--jump-cut <fraction>"
+    pub fn from_args() -> Self {
+        let mut run_args = RunArgs::default();
+        let mut args = std::env::args();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bench-floors" => run_args.bench_floors = true,
+                "--seed" => run_args.seed = number(&mut run_args.problems, &arg, args.next()),
+                "--track" => run_args.track = match args.next().as_deref() {
+                    Some("flat") => Some(floor::track::TrackMode::Flat),
+                    Some("segments") => Some(floor::track::TrackMode::Segments),
+                    Some("noise") => Some(floor::track::TrackMode::Noise),
+                    Some("heightmap") => Some(floor::track::TrackMode::Heightmap),
+                    Some("level") => Some(floor::track::TrackMode::Level),
+                    other => {
+                        run_args.problems.push(format!("unknown track mode {:?}", other));
+                        None
+                    },
+                },
+                "--heightmap" => run_args.heightmap = args.next(),
+                "--level" => run_args.level = args.next(),
+                "--colliders" => run_args.colliders = match args.next().as_deref() {
+                    Some("cuboids") => Some(floor::ColliderMode::Cuboids),
+                    Some("heightfield") => Some(floor::ColliderMode::Heightfield),
+                    other => {
+                        run_args.problems.push(format!("unknown collider mode {:?}", other));
+                        None
+                    },
+                },
+                "--floor-kinds" => run_args.floor_kinds = number(&mut run_args.problems, &arg, args.next()),
+                "--platforms" => run_args.platforms = number(&mut run_args.problems, &arg, args.next()),
+                "--track-shapes" => run_args.track_shapes = number(&mut run_args.problems, &arg, args.next()),
+                "--difficulty" => run_args.difficulty = match args.next().as_deref() {
+                    Some("easy") => Some(difficulty::Preset::Easy),
+                    Some("normal") => Some(difficulty::Preset::Normal),
+                    Some("hard") => Some(difficulty::Preset::Hard),
+                    other => {
+                        run_args.problems.push(format!("unknown difficulty {:?}", other));
+                        None
+                    },
+                },
+                "--extra-jumps" => run_args.extra_jumps = number(&mut run_args.problems, &arg, args.next()),
+                "--coyote-time" => run_args.coyote_time = number(&mut run_args.problems, &arg, args.next()),
+                "--jump-buffer" => run_args.jump_buffer = number(&mut run_args.problems, &arg, args.next()),
+                "--jump-hold" => run_args.jump_hold = number(&mut run_args.problems, &arg, args.next()),
+                "--jump-cut" => run_args.jump_cut = number(&mut run_args.problems, &arg, args.next()),
+                _ => (),
+            }
+        }
+
+        run_args
+    }
+}
+
+fn warn_run_args(run_args: Res<RunArgs>) {
+    for problem in &run_args.problems {
+        warn!("{}", problem);
+    }
+}
+
+fn number<T: std::str::FromStr>(problems: &mut Vec<String>, arg: &str, value: Option<String>) -> Option<T> {
+    let number = value.and_then(|value| value.parse().ok());
+    if number.is_none() {
+        problems.push(format!("{} needs a number", arg));
+    }
+
+    number
+}
+
 fn bootstrap(
     mut assets_handler: asset_loading::AssetsHandler,
     mut game_assets: ResMut<assets::GameAssets>,
@@ -113,16 +230,6 @@ fn window_settings(mut windows: Query<&mut Window, With<PrimaryWindow>>) {
     }
 }
 
-pub fn random_number() -> f32 {
-    let mut rng = thread_rng();
-    let x: f32 = rng.gen();
-    x * 2.0 - 1.0
-}
-
-pub fn random_in_f32_range(low: f32, high: f32) -> f32 {
-    rand::thread_rng().gen_range(low..high)
-}
-
 
 #[derive(Component, Default)]
 struct CleanupMarker;
@@ -145,7 +252,7 @@ fn debug(
     }
 
     if keys.just_pressed(KeyCode::R) {
-        food_event_writer.send(food::SpawnFoodEvent);
+        food_event_writer.send(food::SpawnFoodEvent::default());
     }
 }
 
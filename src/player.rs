@@ -1,10 +1,13 @@
 use crate::{
+    difficulty,
     direction,
     AppState,assets,
     ZeroSignum,
     floor,
     audio,
-    game_over,
+    powerup,
+    rng,
+    RunArgs,
 };
 use bevy::prelude::*;
 use rand::Rng;
@@ -15,11 +18,45 @@ use leafwing_input_manager::axislike::DualAxisData;
 use leafwing_input_manager::plugin::InputManagerSystem;
 use leafwing_input_manager::prelude::*;
 
+pub mod ability;
+pub mod jump;
+pub mod state;
+
+// how hard a bouncy block throws the player up
+static BOUNCE_SPEED: f32 = 8.0;
+// how much of the player's speed a sticky block leaves after a second
+static STICKY_FRICTION: f32 = 0.05;
+static BOOST_TIME: f32 = 1.5;
+static BOOST_MULTIPLIER: f32 = 1.5;
+// how much upward speed a full jump adds
+static JUMP_SPEED: f32 = 5.0;
+static GRAVITY: f32 = 3.0;
+// the step JumpModel works a jump through in
+static NOMINAL_FRAME_TIME: f32 = 1.0 / 60.0;
+pub static PLAYER_HALF_SIZE: f32 = 0.25;
+// how high a ledge the character controller walks straight up
+pub static STEP_HEIGHT: f32 = 1.0;
+// how many rows ahead a shield looks for floor to put a falling player back on
+static RESCUE_ROWS: i32 = 20;
+
 pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
+        let run_args = app.world.resource::<RunArgs>();
+        let ability_settings = ability::AbilitySettings::from_run_args(run_args);
+        let jump_settings = jump::JumpSettings::from_run_args(run_args);
         app.add_plugin(InputManagerPlugin::<PlayerAction>::default())
-            .add_event::<PlayerMoveEvent>();
+            .insert_resource(ability_settings)
+            .insert_resource(jump_settings)
+            .add_event::<PlayerMoveEvent>()
+            .add_event::<state::PlayerStateEvent>()
+            .add_systems((
+                    state::end_run,
+                    state::play_state_sounds,
+                )
+                .after(move_player)
+                .in_set(OnUpdate(AppState::InGame))
+            );
     }
 }
 
@@ -34,44 +71,172 @@ pub struct Player {
     pub friction: f32,
     pub velocity: Vec3,
     pub random: f32,
-    pub state: PlayerState,
+    pub state: state::PlayerState,
     pub death_timer: Option<f32>,
     pub donut_count: usize,
+    // how many of each collectible type, in the order Collectibles has them
+    pub collected: Vec<usize>,
     pub jump_cooldown: f32,
+    // whether Action is down, for how high a jump goes
+    pub jump_held: bool,
+    // how long a jump pressed in the air is still waiting to happen on landing
+    pub jump_buffer: f32,
+    // how long letting go of Action still cuts the current jump short
+    pub jump_hold: f32,
+    pub air_jump_cooldown: f32,
+    pub dash_cooldown: f32,
+    pub pound_cooldown: f32,
+    // mid-air jumps left before landing
+    pub air_jumps_left: usize,
+    pub boost_timer: f32,
+    pub dash_timer: f32,
+    // heading straight down until it lands
+    pub pounding: bool,
 }
 
 impl Player {
-    pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
-
+    pub fn new(run_rng: &mut rng::RunRng) -> Self {
         Player {
             speed: 20.0,
             rotation_speed: 1.0,
             friction: 0.1,
             velocity: Vec3::ZERO,
-            random: rng.gen_range(0.5..1.0),
-            state: PlayerState::Normal,
+            random: run_rng.gen_range(0.5..1.0),
+            state: state::PlayerState::Grounded,
             death_timer: None,
             donut_count: 0,
+            collected: vec!(),
             jump_cooldown: 0.0,
+            jump_held: false,
+            jump_buffer: 0.0,
+            jump_hold: 0.0,
+            air_jump_cooldown: 0.0,
+            dash_cooldown: 0.0,
+            pound_cooldown: 0.0,
+            air_jumps_left: 0,
+            boost_timer: 0.0,
+            dash_timer: 0.0,
+            pounding: false,
         }
     }
 }
 
-pub enum Movement {
-    Normal(direction::Direction),
-    Jump,
+// what move_player does with a jump held all the way, worked through frame by
+// frame from running along flat out so anything placing things on the track
+// knows how high and far the player gets
+pub struct JumpModel {
+    // how fast the player runs along once friction has evened it out
+    pub speed: f32,
+    coyote_time: f32,
+    // where the player is every frame after jumping, along from and up from
+    // where it jumped, until it's dropped a step below where it started
+    path: Vec<Vec2>,
+    // how high above where it jumped from the player can get
+    pub rise: f32,
+    // how long until it's back down to where it jumped from
+    pub air_time: f32,
+}
+
+impl JumpModel {
+    pub fn new(player: &Player, jump_settings: &jump::JumpSettings) -> Self {
+        let path = jump_path(player.speed, player.friction);
+        let landing = path.iter().position(|point| point.y < 0.0).unwrap_or(path.len());
+        JumpModel {
+            speed: cruising_speed(player.speed, player.friction),
+            coyote_time: jump_settings.coyote_time,
+            rise: path.iter().map(|point| point.y).fold(0.0, f32::max),
+            air_time: landing as f32 * NOMINAL_FRAME_TIME,
+            path,
+        }
+    }
+
+    // how far along and how far above where it jumped from the player is
+    // after some time in the air
+    pub fn position_after(&self, seconds: f32) -> Vec2 {
+        let frame = (seconds / NOMINAL_FRAME_TIME).round() as usize;
+        self.path.get(frame).or(self.path.last()).copied().unwrap_or_default()
+    }
+
+    // how far above where it jumped from the player is once it's gone some
+    // way along, it hasn't left the ground yet before that
+    pub fn height_at(&self, distance: f32) -> f32 {
+        if distance <= 0.0 {
+            return 0.0;
+        }
+
+        match self.path.iter().position(|point| point.x >= distance) {
+            Some(frame) => {
+                let before = if frame == 0 { Vec2::ZERO } else { self.path[frame - 1] };
+                let after = self.path[frame];
+                let along = (distance - before.x) / (after.x - before.x).max(f32::EPSILON);
+                before.y + ((after.y - before.y) * along)
+            },
+            // it's dropped further than anything cares about by then
+            None => f32::MIN,
+        }
+    }
+
+    // how far along a jump goes before it's dropped a step below where it started
+    pub fn reach(&self) -> f32 {
+        self.path.last().map_or(0.0, |point| point.x)
+    }
+
+    // the highest ledge the player can get on top of
+    pub fn climb(&self) -> f32 {
+        STEP_HEIGHT + self.rise
+    }
+
+    // how far past a ledge the player can still jump from
+    pub fn coyote_reach(&self) -> f32 {
+        self.speed * self.coyote_time
+    }
+
+    // how far the player has dropped some way past a ledge before it jumps
+    pub fn drop_over(&self, distance: f32) -> f32 {
+        GRAVITY * distance.max(0.0) / self.speed
+    }
 }
 
-#[derive(Reflect, Clone, PartialEq)]
-pub enum PlayerState {
-    Normal,
+// where the player's speed along settles, with friction taking off as much
+// as running adds every frame
+fn cruising_speed(speed: f32, friction: f32) -> f32 {
+    let slowdown = 1.0 - friction.powf(NOMINAL_FRAME_TIME);
+    if slowdown <= 0.0 {
+        speed
+    } else {
+        (speed * NOMINAL_FRAME_TIME / slowdown).min(speed)
+    }
 }
 
-impl Default for PlayerState {
-    fn default() -> PlayerState {
-        PlayerState::Normal
+// a jump from running along, frame by frame the way move_player does it
+fn jump_path(speed: f32, friction: f32) -> Vec<Vec2> {
+    let mut velocity = Vec3::X * cruising_speed(speed, friction);
+    let mut position = Vec3::ZERO;
+    let mut path = vec!();
+    // ten seconds is plenty, without friction a jump might never come down
+    while position.y > -STEP_HEIGHT && path.len() < 600 {
+        velocity *= friction.powf(NOMINAL_FRAME_TIME);
+        velocity += (Vec3::X * speed) * NOMINAL_FRAME_TIME;
+        // no gravity on the frame of the jump
+        let gravity = if path.is_empty() {
+            velocity.y += JUMP_SPEED;
+            Vec3::ZERO
+        } else {
+            GRAVITY * Vec3::new(0.0, -1.0, 0.0)
+        };
+        velocity = velocity.clamp_length_max(speed);
+        position += (gravity + velocity) * NOMINAL_FRAME_TIME;
+        path.push(Vec2::new(position.x, position.y));
     }
+
+    path
+}
+
+pub enum Movement {
+    Normal(direction::Direction),
+    Jump,
+    Dash,
+    Pound,
 }
 
 #[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug)]
@@ -82,6 +247,8 @@ pub enum PlayerAction {
     Right,
 
     Action,
+    Dash,
+    Pound,
 }
 impl PlayerAction {
     const DIRECTIONS: [Self; 4] = [
@@ -110,9 +277,9 @@ pub struct PlayerBundle {
 }
 
 impl PlayerBundle {
-    pub fn new() -> Self {
+    pub fn new(run_rng: &mut rng::RunRng) -> Self {
         PlayerBundle {
-            player: Player::new(),
+            player: Player::new(run_rng),
             input_manager: InputManagerBundle {
                 input_map: PlayerBundle::default_input_map(),
                 action_state: ActionState::default(),
@@ -151,6 +318,16 @@ impl PlayerBundle {
         input_map.insert(KeyCode::Return, Action);
         input_map.insert(GamepadButtonType::South, Action);
 
+        input_map.insert(KeyCode::K, Dash);
+        input_map.insert(KeyCode::LShift, Dash);
+        input_map.insert(GamepadButtonType::West, Dash);
+        input_map.insert(GamepadButtonType::RightTrigger, Dash);
+
+        input_map.insert(KeyCode::L, Pound);
+        input_map.insert(KeyCode::LControl, Pound);
+        input_map.insert(GamepadButtonType::East, Pound);
+        input_map.insert(GamepadButtonType::LeftTrigger, Pound);
+
         input_map
     }
 }
@@ -168,6 +345,7 @@ pub fn handle_input(
     for (entity, action_state, transform, mut player, mut velocity) in &mut players {
         //println!("T: {:?}", transform.translation);
         let mut direction = direction::Direction::NEUTRAL;
+        player.jump_held = action_state.pressed(PlayerAction::Action);
 
         if action_state.just_pressed(PlayerAction::Action) {
             player_move_event_writer.send(PlayerMoveEvent {
@@ -175,6 +353,18 @@ pub fn handle_input(
                 movement: Movement::Jump,
             });
         }
+        if action_state.just_pressed(PlayerAction::Dash) {
+            player_move_event_writer.send(PlayerMoveEvent {
+                entity,
+                movement: Movement::Dash,
+            });
+        }
+        if action_state.just_pressed(PlayerAction::Pound) {
+            player_move_event_writer.send(PlayerMoveEvent {
+                entity,
+                movement: Movement::Pound,
+            });
+        }
         for input_direction in PlayerAction::DIRECTIONS {
             if action_state.pressed(input_direction) {
                 direction += input_direction.direction();
@@ -195,10 +385,17 @@ pub fn move_player(
     time: Res<Time>,
     mut players: Query<(Entity, &mut KinematicCharacterController, &KinematicCharacterControllerOutput, &mut Transform, &mut Player, &mut Velocity), Without<Camera3d>>,
     mut player_move_event_reader: EventReader<PlayerMoveEvent>,
-    mut game_over_event_writer: EventWriter<game_over::GameOverEvent>,
-    floor_manager: Res<floor::FloorManager>,
+    mut player_state_event_writer: EventWriter<state::PlayerStateEvent>,
+    mut floor_manager: ResMut<floor::FloorManager>,
+    floor_colliders: Query<&floor::FloorCollider>,
+    floor_chunks: Query<&floor::FloorChunk>,
+    platforms: Query<&floor::platform::Platform>,
+    difficulty: Res<difficulty::DifficultyProfile>,
     game_assets: Res<assets::GameAssets>,
     mut audio: audio::GameAudio,
+    mut power_ups: ResMut<powerup::PowerUps>,
+    ability_settings: Res<ability::AbilitySettings>,
+    jump_settings: Res<jump::JumpSettings>,
 ) {
     let mut move_events = HashMap::new();
     for move_event in player_move_event_reader.iter() {
@@ -206,12 +403,18 @@ pub fn move_player(
     }
 
     for (entity, mut controller, controller_output, mut transform, mut player, p_velocity) in players.iter_mut() {
+        let mut killed = false;
 
         if p_velocity.linvel.x < player.speed * 0.1 {
-            let current_death_time = player.death_timer.unwrap_or(3.0) - time.delta_seconds();
+            let current_death_time = player.death_timer.unwrap_or(difficulty.stall_time()) - time.delta_seconds();
 
             if current_death_time < 0.0 {
-                game_over_event_writer.send(game_over::GameOverEvent);
+                if power_ups.use_shield() {
+                    // a fresh countdown to get going again
+                    player.death_timer = None;
+                } else {
+                    killed = true;
+                }
             } else {
                 player.death_timer = Some(current_death_time);
             }
@@ -223,23 +426,37 @@ pub fn move_player(
 
         let lowest = floor_manager.get_actual_lowest(); 
         if transform.translation.y < lowest - 3.0 {
-            game_over_event_writer.send(game_over::GameOverEvent);
+            match rescue_position(&floor_manager, transform.translation) {
+                Some(rescue) if power_ups.use_shield() => {
+                    transform.translation = rescue;
+                    player.velocity.y = 0.0;
+                },
+                _ => killed = true,
+            }
         }
 
         let speed: f32 = player.speed;
         let rotation_speed: f32 = player.rotation_speed;
         let friction: f32 = player.friction;
-        let mut gravity: Vec3 = 3.0 * Vec3::new(0.0, -1.0, 0.0);
+        let mut gravity: Vec3 = GRAVITY * Vec3::new(0.0, -1.0, 0.0);
 
         player.velocity *= friction.powf(time.delta_seconds());
         player.velocity += (Vec3::X * speed) * time.delta_seconds();
 
         if controller_output.grounded {
-            player.jump_cooldown = player.jump_cooldown.max(0.2);
+            player.jump_cooldown = player.jump_cooldown.max(jump_settings.coyote_time);
+            player.air_jumps_left = ability_settings.extra_jumps;
         }
         player.jump_cooldown -= time.delta_seconds();
         player.jump_cooldown = player.jump_cooldown.clamp(-3.0, 3.0);
-
+        player.jump_buffer = (player.jump_buffer - time.delta_seconds()).max(0.0);
+        player.air_jump_cooldown = (player.air_jump_cooldown - time.delta_seconds()).max(0.0);
+        player.dash_cooldown = (player.dash_cooldown - time.delta_seconds()).max(0.0);
+        player.pound_cooldown = (player.pound_cooldown - time.delta_seconds()).max(0.0);
+
+        // a jump pressed just before landing goes as soon as it's grounded or
+        // in coyote time, ahead of the press below so that can't spend it as an air jump
+        let mut jumped = player.jump_buffer > 0.0 && player.jump_cooldown > 0.0;
         if let Some(move_event) = move_events.get(&entity) {
             match move_event.movement {
                 Movement::Normal(direction) => {
@@ -250,18 +467,110 @@ pub fn move_player(
                     player.velocity += (acceleration * speed) * time.delta_seconds();
                 },
                 Movement::Jump => {
-                    if player.jump_cooldown > 0.0 {
-                        println!("JUMP");
-                        player.jump_cooldown = 0.0;
-                        audio.play_sfx(&game_assets.jump);
-                        player.velocity += Vec3::new(0.0, 300.0, 0.0) * time.delta_seconds();
-                        gravity = Vec3::ZERO;
+                    if jumped || player.jump_cooldown > 0.0 {
+                        jumped = true;
+                    } else if player.air_jumps_left > 0 && player.air_jump_cooldown <= 0.0 {
+                        player.air_jumps_left -= 1;
+                        player.air_jump_cooldown = ability_settings.air_jump_cooldown;
+                        player.pounding = false;
+                        // from hanging still, not fighting however fast it was falling
+                        player.velocity.y = player.velocity.y.max(0.0);
+                        jumped = true;
+                    } else {
+                        player.jump_buffer = jump_settings.buffer_time;
+                    }
+                },
+                Movement::Dash => {
+                    if !controller_output.grounded && player.dash_cooldown <= 0.0 {
+                        player.dash_cooldown = ability_settings.dash_cooldown;
+                        player.dash_timer = ability::DASH_TIME;
+                        player.pounding = false;
+                        audio.play_sfx_pitched(&game_assets.jump, 1.5);
+                        player.velocity.x = speed * ability::DASH_MULTIPLIER;
+                        player.velocity.y = 0.0;
+                    }
+                },
+                Movement::Pound => {
+                    if !controller_output.grounded && !player.pounding && player.pound_cooldown <= 0.0 {
+                        player.pound_cooldown = ability_settings.pound_cooldown;
+                        player.dash_timer = 0.0;
+                        player.pounding = true;
                     }
                 },
             }
         }
 
-        player.velocity = player.velocity.clamp_length_max(speed);
+        if jumped {
+            player.jump_cooldown = 0.0;
+            player.jump_buffer = 0.0;
+            player.jump_hold = jump_settings.hold_time;
+            audio.play_sfx(&game_assets.jump);
+            player.velocity.y += JUMP_SPEED;
+            gravity = Vec3::ZERO;
+        }
+
+        // letting go of Action early cuts the jump short, holding it the
+        // whole hold_time gets the full jump
+        if player.jump_hold > 0.0 {
+            player.jump_hold = (player.jump_hold - time.delta_seconds()).max(0.0);
+            if !player.jump_held {
+                player.jump_hold = 0.0;
+                if player.velocity.y > 0.0 {
+                    player.velocity.y *= jump_settings.release_cut;
+                }
+            }
+        }
+
+        let pounded = player.pounding && controller_output.grounded;
+
+        // whatever the player ran into this frame
+        for (cell, kind) in floor::kind::touched_blocks(controller_output, &floor_manager, &floor_colliders, &floor_chunks) {
+            if pounded {
+                floor_manager.pound(cell, kind);
+            }
+            match kind {
+                floor::kind::FloorKind::Normal => (),
+                floor::kind::FloorKind::Crumbling => floor_manager.start_crumbling(cell),
+                floor::kind::FloorKind::Bouncy => {
+                    let bounce = if pounded { BOUNCE_SPEED * ability::POUND_BOUNCE } else { BOUNCE_SPEED };
+                    player.velocity.y = player.velocity.y.max(bounce);
+                    gravity = Vec3::ZERO;
+                },
+                floor::kind::FloorKind::Sticky => {
+                    player.velocity *= STICKY_FRICTION.powf(time.delta_seconds());
+                },
+                floor::kind::FloorKind::Boost => player.boost_timer = BOOST_TIME,
+                floor::kind::FloorKind::Hazard => killed = true,
+            }
+        }
+
+        if pounded {
+            player.pounding = false;
+            player.velocity.y = player.velocity.y.max(0.0);
+            audio.play_sfx_pitched(&game_assets.jump, 0.5);
+        }
+
+        player.boost_timer = (player.boost_timer - time.delta_seconds()).max(0.0);
+        let top_speed = if player.boost_timer > 0.0 {
+            player.velocity += (Vec3::X * speed) * time.delta_seconds();
+            speed * BOOST_MULTIPLIER
+        } else {
+            speed
+        };
+
+        // a dash hangs in the air going faster than the player otherwise can
+        player.dash_timer = (player.dash_timer - time.delta_seconds()).max(0.0);
+        let top_speed = if player.dash_timer > 0.0 {
+            gravity = Vec3::ZERO;
+            top_speed.max(speed * ability::DASH_MULTIPLIER)
+        } else {
+            top_speed
+        };
+
+        player.velocity = player.velocity.clamp_length_max(top_speed);
+        if player.pounding {
+            player.velocity.y = -ability::POUND_SPEED;
+        }
 
 //      player.velocity.z *= if player.velocity.x > 0.0 { 1.0 } else { 0.0 };
 //      player.velocity.y *= if player.velocity.x > 0.0 { 1.0 } else { 0.0 };
@@ -273,7 +582,22 @@ pub fn move_player(
             .atan2(new_translation.x - transform.translation.x);
         let rotation = Quat::from_axis_angle(Vec3::Y, angle);
 //       velocity.angvel = rotation.to_scaled_axis();
-        controller.translation = Some(new_translation);
+        // platforms carry along whatever's on them
+        let ride = controller_output.collisions
+            .iter()
+            .find_map(|collision| platforms.get(collision.entity).ok())
+            .map(|platform| platform.delta)
+            .unwrap_or(Vec3::ZERO);
+        controller.translation = Some(new_translation + ride);
+
+        let inputs = state::StateInputs {
+            grounded: controller_output.grounded,
+            rising: new_translation.y > 0.0,
+            stalled: player.death_timer.is_some(),
+            out_of_bounds: !controller_output.grounded && transform.translation.y < floor_manager.get_actual_lowest(),
+            killed,
+        };
+        state::advance(entity, &mut player.state, inputs, &mut player_state_event_writer);
 //        velocity.linvel = player.velocity * time.delta_seconds();
 
 //        transform.translation.x = 0.0; // hardcoding for now
@@ -289,13 +613,25 @@ pub fn move_player(
     }
 }
 
+// just over the nearest block at or a little ahead of where the player fell
+fn rescue_position(floor_manager: &floor::FloorManager, translation: Vec3) -> Option<Vec3> {
+    (0..RESCUE_ROWS).find_map(|row| {
+        let x = translation.x + (row as f32 * floor::FLOOR_CUBE_SIZE);
+        floor_manager
+            .column_profile(x)
+            .into_iter()
+            .min_by(|(a, _), (b, _)| (a - translation.z).abs().total_cmp(&(b - translation.z).abs()))
+            .map(|(z, top)| Vec3::new(x, top + PLAYER_HALF_SIZE + STEP_HEIGHT, z))
+    })
+}
+
 pub fn spin_mesh( 
-    player: Query<(&Velocity, &Player)>,
+    player: Query<&Player>,
     time: Res<Time>,
     mut inner_mesh: Query<&mut Transform, With<InnerMesh>>,
 ) {
-    for (v, player) in &player {
-        if v.linvel.x > player.speed * 0.1 {
+    for player in &player {
+        if player.state.is_moving() {
             for mut i in &mut inner_mesh {
                 i.rotate_z(time.delta_seconds() * player.speed);
             }
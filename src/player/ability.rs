@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+use crate::RunArgs;
+
+// an air dash goes this many times the player's speed for DASH_TIME, no gravity
+pub static DASH_MULTIPLIER: f32 = 2.0;
+pub static DASH_TIME: f32 = 0.25;
+// how fast a ground pound drives the player down
+pub static POUND_SPEED: f32 = 18.0;
+// how much shorter a block gets when it's pounded, in half-cubes like Floor::height
+pub static POUND_DEPTH: f32 = 1.0;
+// pounding a bouncy block throws the player this much higher than landing on it
+pub static POUND_BOUNCE: f32 = 1.5;
+
+#[derive(Resource, Clone)]
+pub struct AbilitySettings {
+    // jumps the player gets in mid-air before it has to land again
+    pub extra_jumps: usize,
+    // seconds before each can be used again
+    pub air_jump_cooldown: f32,
+    pub dash_cooldown: f32,
+    pub pound_cooldown: f32,
+}
+
+impl Default for AbilitySettings {
+    fn default() -> Self {
+        AbilitySettings {
+            extra_jumps: 1,
+            air_jump_cooldown: 0.25,
+            dash_cooldown: 1.5,
+            pound_cooldown: 1.0,
+        }
+    }
+}
+
+impl AbilitySettings {
+    // takes "--extra-jumps" from the command line, see RunArgs
+    pub fn from_run_args(run_args: &RunArgs) -> Self {
+        let mut ability_settings = AbilitySettings::default();
+        if let Some(jumps) = run_args.extra_jumps {
+            ability_settings.extra_jumps = jumps;
+        }
+
+        ability_settings
+    }
+}
@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+use crate::RunArgs;
+
+#[derive(Resource, Clone)]
+pub struct JumpSettings {
+    // how long after running off a ledge a jump still works
+    pub coyote_time: f32,
+    // how long before landing a jump press still gets a jump on landing
+    pub buffer_time: f32,
+    // how long holding Action keeps the jump going, letting go any later
+    // than this gets the full jump
+    pub hold_time: f32,
+    // how much of its upward speed a jump keeps when Action is let go early
+    pub release_cut: f32,
+}
+
+impl Default for JumpSettings {
+    fn default() -> Self {
+        JumpSettings {
+            coyote_time: 0.2,
+            buffer_time: 0.15,
+            hold_time: 0.25,
+            release_cut: 0.5,
+        }
+    }
+}
+
+impl JumpSettings {
+    // takes "--coyote-time", "--jump-buffer", "--jump-hold" and "--jump-cut"
+    // from the command line, see RunArgs
+    pub fn from_run_args(run_args: &RunArgs) -> Self {
+        let defaults = JumpSettings::default();
+        JumpSettings {
+            coyote_time: run_args.coyote_time.unwrap_or(defaults.coyote_time),
+            buffer_time: run_args.jump_buffer.unwrap_or(defaults.buffer_time),
+            hold_time: run_args.jump_hold.unwrap_or(defaults.hold_time),
+            release_cut: run_args.jump_cut.unwrap_or(defaults.release_cut),
+        }
+    }
+}
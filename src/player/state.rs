@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+use crate::{assets, audio, game_over};
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PlayerState {
+    #[default]
+    Grounded,
+    // in the air and still going up
+    Jumping,
+    // in the air and coming down
+    Falling,
+    // going too slowly, the stall countdown is running
+    Stalled,
+    // dropped below the whole track, only a shield can save it now
+    Dying,
+    // the run is over
+    Dead,
+}
+
+// what the state machine goes on, move_player works these out every frame
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StateInputs {
+    pub grounded: bool,
+    pub rising: bool,
+    pub stalled: bool,
+    pub out_of_bounds: bool,
+    // fell too far, hit a hazard or stalled for too long
+    pub killed: bool,
+}
+
+impl PlayerState {
+    // there's no coming back from dead, everything else goes by the inputs
+    // with the worst of them winning
+    pub fn next(self, inputs: StateInputs) -> PlayerState {
+        if self == PlayerState::Dead || inputs.killed {
+            PlayerState::Dead
+        } else if inputs.out_of_bounds {
+            PlayerState::Dying
+        } else if inputs.stalled {
+            PlayerState::Stalled
+        } else if inputs.grounded {
+            PlayerState::Grounded
+        } else if inputs.rising {
+            PlayerState::Jumping
+        } else {
+            PlayerState::Falling
+        }
+    }
+
+    // still going along the track rather than stuck or finished
+    pub fn is_moving(self) -> bool {
+        matches!(self, PlayerState::Grounded | PlayerState::Jumping | PlayerState::Falling | PlayerState::Dying)
+    }
+}
+
+// sent whenever a player's state changes
+pub struct PlayerStateEvent {
+    pub entity: Entity,
+    pub from: PlayerState,
+    pub to: PlayerState,
+}
+
+// moves the player to its next state, saying so if it changed
+pub fn advance(
+    entity: Entity,
+    state: &mut PlayerState,
+    inputs: StateInputs,
+    player_state_event_writer: &mut EventWriter<PlayerStateEvent>,
+) {
+    let next = state.next(inputs);
+    if next != *state {
+        player_state_event_writer.send(PlayerStateEvent { entity, from: *state, to: next });
+        *state = next;
+    }
+}
+
+pub fn end_run(
+    mut player_state_events: EventReader<PlayerStateEvent>,
+    mut game_over_event_writer: EventWriter<game_over::GameOverEvent>,
+) {
+    if player_state_events.iter().any(|event| event.to == PlayerState::Dead) {
+        game_over_event_writer.send(game_over::GameOverEvent);
+    }
+}
+
+// a warning when the stall countdown starts and when the player drops out
+// of the track, jumps and the rest make their own sounds in move_player
+pub fn play_state_sounds(
+    mut player_state_events: EventReader<PlayerStateEvent>,
+    mut audio: audio::GameAudio,
+    game_assets: Res<assets::GameAssets>,
+) {
+    for event in player_state_events.iter() {
+        match event.to {
+            PlayerState::Stalled => audio.play_sfx(&game_assets.blip),
+            PlayerState::Dying => audio.play_sfx(&game_assets.game_over),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlayerState::*, *};
+
+    #[test]
+    fn states_follow_the_inputs() {
+        let airborne = StateInputs::default();
+        let grounded = StateInputs { grounded: true, ..default() };
+        let transitions = [
+            (Grounded, StateInputs { rising: true, ..airborne }, Jumping),
+            (Jumping, airborne, Falling),
+            (Falling, grounded, Grounded),
+            (Grounded, StateInputs { stalled: true, ..grounded }, Stalled),
+            (Stalled, grounded, Grounded),
+            (Falling, StateInputs { out_of_bounds: true, ..airborne }, Dying),
+            // a shield put it back on the track
+            (Dying, grounded, Grounded),
+            (Dying, StateInputs { killed: true, ..airborne }, Dead),
+            (Stalled, StateInputs { stalled: true, killed: true, ..grounded }, Dead),
+            (Dead, grounded, Dead),
+        ];
+        for (from, inputs, to) in transitions {
+            assert_eq!(from.next(inputs), to, "{:?} with {:?}", from, inputs);
+        }
+    }
+
+    #[test]
+    fn advance_says_when_the_state_changes() {
+        let entity = Entity::from_raw(7);
+        let airborne = StateInputs::default();
+        let grounded = StateInputs { grounded: true, ..default() };
+        let steps = [
+            grounded,
+            grounded,
+            StateInputs { rising: true, ..airborne },
+            StateInputs { rising: true, ..airborne },
+            airborne,
+            airborne,
+            grounded,
+        ];
+
+        let mut app = App::new();
+        app.add_event::<PlayerStateEvent>()
+            .add_system(move |mut state: Local<PlayerState>, mut player_state_event_writer: EventWriter<PlayerStateEvent>| {
+                for inputs in steps {
+                    advance(entity, &mut state, inputs, &mut player_state_event_writer);
+                }
+            });
+        app.update();
+
+        let events = app.world.resource::<Events<PlayerStateEvent>>();
+        let sent = events
+            .get_reader()
+            .iter(events)
+            .map(|event| (event.entity, event.from, event.to))
+            .collect::<Vec<_>>();
+        // staying put says nothing, every real change says so once
+        assert_eq!(sent, vec!(
+            (entity, Grounded, Jumping),
+            (entity, Jumping, Falling),
+            (entity, Falling, Grounded),
+        ));
+    }
+}
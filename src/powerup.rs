@@ -0,0 +1,209 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::{food, AppState};
+
+// how fast everything runs during slow-motion
+static SLOW_MOTION_SPEED: f32 = 0.5;
+static BOOST_SPEED_MULTIPLIER: f32 = 1.3;
+
+pub struct PowerUpPlugin;
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<PowerUps>()
+            .add_system(start_power_ups.in_schedule(OnEnter(AppState::InGame)))
+            .add_system(update_power_ups.in_set(OnUpdate(AppState::InGame)))
+            .add_system(collect_power_ups.after(food::collect_food).in_set(OnUpdate(AppState::InGame)))
+            .add_system(reset_time.in_schedule(OnExit(AppState::InGame)));
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerUp {
+    // update_food pulls nearby donuts in
+    Magnet,
+    // the next fall or stall doesn't end the run
+    Shield,
+    // everything but the power-up timers runs slower
+    SlowMotion,
+    // raises Player::speed
+    Boost,
+}
+
+impl PowerUp {
+    pub fn duration(self) -> f32 {
+        match self {
+            PowerUp::Magnet => 8.0,
+            PowerUp::Shield => 10.0,
+            PowerUp::SlowMotion => 4.0,
+            PowerUp::Boost => 5.0,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUp::Magnet => "Magnet",
+            PowerUp::Shield => "Shield",
+            PowerUp::SlowMotion => "Slow-Mo",
+            PowerUp::Boost => "Boost",
+        }
+    }
+}
+
+// the power-ups going right now and how long each has left, in real seconds.
+// different power-ups all run at once. picking up one that's already going
+// starts its timer over at the full duration rather than adding to it, and
+// never makes it any stronger: a shield still only forgives one fall or stall,
+// slow-motion stays at SLOW_MOTION_SPEED and boost at BOOST_SPEED_MULTIPLIER
+#[derive(Resource, Default)]
+pub struct PowerUps {
+    active: Vec<(PowerUp, f32)>,
+}
+
+impl PowerUps {
+    pub fn collect(&mut self, power_up: PowerUp) {
+        let duration = power_up.duration();
+        match self.active.iter_mut().find(|(active, _)| *active == power_up) {
+            Some((_, left)) => *left = duration,
+            None => self.active.push((power_up, duration)),
+        }
+    }
+
+    pub fn tick(&mut self, seconds: f32) {
+        for (_, left) in self.active.iter_mut() {
+            *left -= seconds;
+        }
+        self.active.retain(|(_, left)| *left > 0.0);
+    }
+
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.time_left(power_up).is_some()
+    }
+
+    pub fn time_left(&self, power_up: PowerUp) -> Option<f32> {
+        self.active
+            .iter()
+            .find(|(active, _)| *active == power_up)
+            .map(|(_, left)| *left)
+    }
+
+    // in the order they were picked up
+    pub fn active(&self) -> impl Iterator<Item = (PowerUp, f32)> + '_ {
+        self.active.iter().copied()
+    }
+
+    // true if there was a shield to take the hit, which uses it up
+    pub fn use_shield(&mut self) -> bool {
+        let before = self.active.len();
+        self.active.retain(|(active, _)| *active != PowerUp::Shield);
+        self.active.len() != before
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        if self.is_active(PowerUp::SlowMotion) { SLOW_MOTION_SPEED } else { 1.0 }
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.is_active(PowerUp::Boost) { BOOST_SPEED_MULTIPLIER } else { 1.0 }
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+}
+
+fn start_power_ups(mut power_ups: ResMut<PowerUps>, mut time: ResMut<Time>) {
+    power_ups.clear();
+    time.set_relative_speed(1.0);
+}
+
+// counts down on the real clock so slow-motion doesn't make itself last longer
+fn update_power_ups(mut power_ups: ResMut<PowerUps>, mut time: ResMut<Time>) {
+    power_ups.tick(time.raw_delta_seconds());
+    time.set_relative_speed(power_ups.time_scale());
+}
+
+fn collect_power_ups(
+    mut power_ups: ResMut<PowerUps>,
+    mut food_collected_events: EventReader<food::FoodCollectedEvent>,
+    collectibles: Res<food::collectible::Collectibles>,
+) {
+    for event in food_collected_events.iter() {
+        if let Some(power_up) = collectibles.effect(event.kind) {
+            power_ups.collect(power_up);
+        }
+    }
+}
+
+fn reset_time(mut time: ResMut<Time>) {
+    time.set_relative_speed(1.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picking_one_up_again_refreshes_its_timer() {
+        let mut power_ups = PowerUps::default();
+        power_ups.collect(PowerUp::Magnet);
+        power_ups.tick(3.0);
+        power_ups.collect(PowerUp::Magnet);
+        assert_eq!(power_ups.time_left(PowerUp::Magnet), Some(PowerUp::Magnet.duration()));
+
+        // straight away too, it doesn't add up
+        power_ups.collect(PowerUp::Magnet);
+        assert_eq!(power_ups.time_left(PowerUp::Magnet), Some(PowerUp::Magnet.duration()));
+        assert_eq!(power_ups.active().count(), 1);
+    }
+
+    #[test]
+    fn different_ones_run_at_once() {
+        let mut power_ups = PowerUps::default();
+        power_ups.collect(PowerUp::Boost);
+        power_ups.tick(1.0);
+        power_ups.collect(PowerUp::SlowMotion);
+
+        assert_eq!(
+            power_ups.active().collect::<Vec<_>>(),
+            vec!(
+                (PowerUp::Boost, PowerUp::Boost.duration() - 1.0),
+                (PowerUp::SlowMotion, PowerUp::SlowMotion.duration()),
+            ),
+        );
+        assert_eq!(power_ups.time_scale(), SLOW_MOTION_SPEED);
+        assert_eq!(power_ups.speed_multiplier(), BOOST_SPEED_MULTIPLIER);
+    }
+
+    #[test]
+    fn a_shield_takes_one_hit() {
+        let mut power_ups = PowerUps::default();
+        power_ups.collect(PowerUp::Shield);
+        power_ups.collect(PowerUp::Shield);
+        power_ups.collect(PowerUp::Magnet);
+
+        assert!(power_ups.use_shield());
+        assert!(!power_ups.is_active(PowerUp::Shield));
+        assert!(!power_ups.use_shield());
+        // nothing else goes with it
+        assert!(power_ups.is_active(PowerUp::Magnet));
+    }
+
+    #[test]
+    fn effects_wear_off() {
+        let mut power_ups = PowerUps::default();
+        power_ups.collect(PowerUp::SlowMotion);
+        power_ups.collect(PowerUp::Boost);
+        assert_eq!(power_ups.time_scale(), SLOW_MOTION_SPEED);
+        assert_eq!(power_ups.speed_multiplier(), BOOST_SPEED_MULTIPLIER);
+
+        power_ups.tick(PowerUp::SlowMotion.duration());
+        assert_eq!(power_ups.time_scale(), 1.0);
+        assert_eq!(power_ups.speed_multiplier(), BOOST_SPEED_MULTIPLIER);
+
+        power_ups.tick(PowerUp::Boost.duration());
+        assert_eq!(power_ups.time_scale(), 1.0);
+        assert_eq!(power_ups.speed_multiplier(), 1.0);
+        assert_eq!(power_ups.active().count(), 0);
+    }
+}
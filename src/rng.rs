@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+
+// every random decision in a run goes through this so a run
+// can be replayed exactly by starting it with the same seed
+#[derive(Resource)]
+pub struct RunRng {
+    pub seed: u64,
+    pub requested_seed: Option<u64>,
+    rng: StdRng,
+}
+
+impl Default for RunRng {
+    fn default() -> Self {
+        RunRng::from_seed(rand::thread_rng().gen())
+    }
+}
+
+impl RunRng {
+    pub fn from_seed(seed: u64) -> Self {
+        RunRng {
+            seed,
+            requested_seed: None,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // takes the seed asked for with "--seed", if any
+    pub fn from_run_args(run_args: &crate::RunArgs) -> Self {
+        RunRng {
+            requested_seed: run_args.seed,
+            ..default()
+        }
+    }
+
+    // uses the requested seed if there is one, otherwise rolls a new one
+    pub fn start_run(&mut self) {
+        let seed = self.requested_seed.unwrap_or_else(|| rand::thread_rng().gen());
+        self.reseed(seed);
+    }
+
+    // a separate generator derived from the run's seed, for things like
+    // the track layout whose draws shouldn't depend on how many times
+    // everything else has rolled
+    pub fn stream(&self, stream: u64) -> RunRng {
+        RunRng::from_seed(self.seed ^ stream)
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // between -1.0 and 1.0
+    pub fn random_number(&mut self) -> f32 {
+        let x: f32 = self.rng.gen();
+        x * 2.0 - 1.0
+    }
+
+    pub fn random_in_f32_range(&mut self, low: f32, high: f32) -> f32 {
+        self.rng.gen_range(low..high)
+    }
+}
+
+impl RngCore for RunRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    asset_loading, assets::GameAssets, audio::GameAudio, cleanup, menus, floor, player,
+    asset_loading, assets::GameAssets, audio::GameAudio, cleanup, menus, floor, player, difficulty,
     ui::text_size, AppState, menus::HOVERED_BUTTON, menus::NORMAL_BUTTON, assets,
 };
 use bevy::app::AppExit;
@@ -23,6 +23,9 @@ impl Plugin for TitlePlugin {
 #[derive(Component)]
 struct TitleScreenCleanupMarker;
 
+#[derive(Component)]
+struct DifficultyLabel;
+
 #[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug)]
 pub enum MenuAction {
     Up,
@@ -88,6 +91,7 @@ fn setup(
     mut audio: GameAudio,
     mut clear_color: ResMut<ClearColor>,
     text_scaler: text_size::TextScaler,
+    difficulty: Res<difficulty::DifficultyProfile>,
 ) {
     commands
         .spawn(InputManagerBundle {
@@ -177,6 +181,31 @@ fn setup(
         })
         .insert(TitleScreenCleanupMarker);
 
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexStart,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(5.0),
+                    right: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::from_section(
+                difficulty_label(difficulty.preset),
+                TextStyle {
+                    font: game_assets.font.clone(),
+                    font_size: text_scaler.scale(menus::BY_LINE_FONT_SIZE),
+                    color: Color::rgba(0.0, 0.0, 0.0, 1.0),
+                }
+            ),
+            ..Default::default()
+        })
+        .insert(DifficultyLabel)
+        .insert(TitleScreenCleanupMarker);
+
     commands
         .spawn(NodeBundle {
             style: Style {
@@ -270,6 +299,8 @@ fn update_menu_buttons(
     mut assets_handler: asset_loading::AssetsHandler,
     mut game_assets: ResMut<assets::GameAssets>,
     mut floor_manager: ResMut<floor::FloorManager>,
+    mut difficulty: ResMut<difficulty::DifficultyProfile>,
+    mut difficulty_labels: Query<&mut Text, With<DifficultyLabel>>,
     time: Res<Time>,
 ) {
 //  if floor_manager.title_screen_cooldown < 1.0 {
@@ -297,6 +328,19 @@ fn update_menu_buttons(
         };
     }
 
+    // left and right pick the difficulty for the next run
+    if action_state.just_pressed(MenuAction::Left) || action_state.just_pressed(MenuAction::Right) {
+        audio.play_sfx(&game_assets.blip);
+        difficulty.preset = if action_state.just_pressed(MenuAction::Left) {
+            difficulty.preset.previous()
+        } else {
+            difficulty.preset.next()
+        };
+
+        for mut text in &mut difficulty_labels {
+            text.sections[0].value = difficulty_label(difficulty.preset);
+        }
+    }
 
     for (i, mut color) in button_colors.iter_mut().enumerate() {
         if i == *selected_button {
@@ -316,3 +360,7 @@ fn update_menu_buttons(
         }
     }
 }
+
+fn difficulty_label(preset: difficulty::Preset) -> String {
+    format!("< Difficulty: {:?} >", preset)
+}